@@ -1,15 +1,12 @@
-use std::time::SystemTime;
-
 use criterion::{criterion_group, criterion_main, Criterion};
-use rdev::Event;
-use rshotkey::history;
+use rshotkey::{
+    event::{Edge, Event, EventKind},
+    history,
+    key::Key,
+};
 
 fn insert_benchmark(c: &mut Criterion) {
-    let event = Event {
-        time: SystemTime::now(),
-        name: None,
-        event_type: rdev::EventType::KeyPress(rdev::Key::Alt),
-    };
+    let event = Event::new(EventKind::Key(Key::KeyLeftAlt, Edge::Press));
     c.bench_function("history_insert", |b| {
         b.iter(|| {
             let mut history = history::History::new(256);
@@ -0,0 +1,93 @@
+#![no_main]
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use arbitrary::{Result, Unstructured};
+use futures::FutureExt;
+use libfuzzer_sys::fuzz_target;
+use rdev::{Button, EventType};
+use rshotkey::{
+    event::Event,
+    history::History,
+    hooks::{hook_keyset, match_keyset},
+    key::{BindKey, KeySet},
+    listener::{HookContext, HookResult},
+};
+use tokio::sync::RwLock;
+
+const KEYS: [rdev::Key; 8] = [
+    rdev::Key::KeyA,
+    rdev::Key::KeyB,
+    rdev::Key::KeyC,
+    rdev::Key::ControlLeft,
+    rdev::Key::ShiftLeft,
+    rdev::Key::Alt,
+    rdev::Key::Num1,
+    rdev::Key::Unknown(0),
+];
+
+fn key(u: &mut Unstructured) -> Result<rdev::Key> {
+    u.choose(&KEYS).copied()
+}
+
+fn event_type(u: &mut Unstructured) -> Result<EventType> {
+    Ok(match u.int_in_range(0..=4)? {
+        0 => EventType::KeyRelease(key(u)?),
+        1 => EventType::ButtonPress(Button::Left),
+        2 => EventType::MouseMove { x: 0.0, y: 0.0 },
+        _ => EventType::KeyPress(key(u)?),
+    })
+}
+
+fn key_set(u: &mut Unstructured) -> Result<KeySet> {
+    let mut key_set = KeySet::default();
+    for _ in 0..u.int_in_range(0..=4)? {
+        let mut events = vec![];
+        for _ in 0..u.int_in_range(0..=3)? {
+            events.push(EventType::KeyPress(key(u)?));
+        }
+        let delay = Duration::from_millis(u.int_in_range(0..=500)?);
+        key_set = key_set.bind(BindKey::from(events).delay(delay));
+    }
+    // A settle time makes `hook_keyset` sleep, it is left at zero.
+    Ok(key_set.deadline(Duration::from_millis(u.int_in_range(0..=1000)?)))
+}
+
+fn history(u: &mut Unstructured) -> Result<History> {
+    let mut history = History::new(256);
+    let mut time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+    for _ in 0..u.int_in_range(0..=16)? {
+        let gap = Duration::from_millis(u.int_in_range(0..=1_000)?);
+        time = if u.arbitrary()? { time + gap } else { time - gap };
+        history.push(Event::new(event_type(u)?.into()).at(time));
+    }
+    Ok(history)
+}
+
+fn noop(_: HookContext) -> HookResult {
+    async { Ok(()) }.boxed()
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut u = Unstructured::new(data);
+    let (Ok(key_set), Ok(history)) = (key_set(&mut u), history(&mut u)) else {
+        return;
+    };
+
+    match_keyset(&key_set, &history);
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_time()
+        .build()
+        .unwrap();
+    runtime
+        .block_on(hook_keyset(
+            key_set,
+            history.clone(),
+            Arc::new(RwLock::new(history)),
+            noop,
+        ))
+        .unwrap();
+});
@@ -1,3 +1,155 @@
-pub enum Event {
-    
-}
\ No newline at end of file
+use std::time::SystemTime;
+
+use crate::key::Key;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edge {
+    Press,
+    Release,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Button {
+    Left,
+    Right,
+    Middle,
+    Unknown(u8),
+}
+
+impl From<rdev::Button> for Button {
+    fn from(value: rdev::Button) -> Self {
+        match value {
+            rdev::Button::Left => Button::Left,
+            rdev::Button::Right => Button::Right,
+            rdev::Button::Middle => Button::Middle,
+            rdev::Button::Unknown(code) => Button::Unknown(code),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EventKind {
+    Key(Key, Edge),
+    Button(Button, Edge),
+    Wheel { delta_x: i64, delta_y: i64 },
+    Move { x: f64, y: f64 },
+}
+
+impl From<rdev::EventType> for EventKind {
+    fn from(value: rdev::EventType) -> Self {
+        match value {
+            rdev::EventType::KeyPress(key) => EventKind::Key(key.into(), Edge::Press),
+            rdev::EventType::KeyRelease(key) => EventKind::Key(key.into(), Edge::Release),
+            rdev::EventType::ButtonPress(button) => EventKind::Button(button.into(), Edge::Press),
+            rdev::EventType::ButtonRelease(button) => {
+                EventKind::Button(button.into(), Edge::Release)
+            }
+            rdev::EventType::Wheel { delta_x, delta_y } => EventKind::Wheel { delta_x, delta_y },
+            rdev::EventType::MouseMove { x, y } => EventKind::Move { x, y },
+        }
+    }
+}
+
+/// An input event as recorded in [`History`](crate::history::History).
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Event {
+    pub kind: EventKind,
+    pub time: SystemTime,
+    /// Identifier of the originating device, when the backend reports one.
+    pub device_id: Option<u32>,
+    /// Whether the event was injected by software rather than a device.
+    pub synthetic: bool,
+    /// Text produced by the event, if any.
+    pub text: Option<String>,
+}
+
+impl Event {
+    pub fn new(kind: EventKind) -> Self {
+        Self {
+            kind,
+            time: SystemTime::now(),
+            device_id: None,
+            synthetic: false,
+            text: None,
+        }
+    }
+
+    pub fn at(&mut self, time: SystemTime) -> Self {
+        self.time = time;
+        self.to_owned()
+    }
+
+    pub fn edge(&self) -> Option<Edge> {
+        match self.kind {
+            EventKind::Key(_, edge) | EventKind::Button(_, edge) => Some(edge),
+            _ => None,
+        }
+    }
+
+    /// The key or mouse button this event acts on.
+    pub fn key(&self) -> Option<Key> {
+        match self.kind {
+            EventKind::Key(key, _) => Some(key),
+            EventKind::Button(button, _) => Some(button.into()),
+            _ => None,
+        }
+    }
+
+    /// The key this event presses, `None` for releases and pointer events.
+    pub fn pressed_key(&self) -> Option<Key> {
+        match self.edge() {
+            Some(Edge::Press) => self.key(),
+            _ => None,
+        }
+    }
+}
+
+impl From<rdev::Event> for Event {
+    fn from(value: rdev::Event) -> Self {
+        Self {
+            kind: value.event_type.into(),
+            time: value.time,
+            device_id: None,
+            synthetic: false,
+            text: value.name,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_from_rdev() {
+        let time = SystemTime::now();
+        let event: Event = rdev::Event {
+            time,
+            name: Some("a".to_string()),
+            event_type: rdev::EventType::KeyPress(rdev::Key::KeyA),
+        }
+        .into();
+        assert_eq!(event.kind, EventKind::Key(Key::KeyA, Edge::Press));
+        assert_eq!(event.time, time);
+        assert_eq!(event.text.as_deref(), Some("a"));
+        assert!(!event.synthetic);
+    }
+
+    #[test]
+    fn test_event_pressed_key() {
+        let press = Event::new(EventKind::Button(Button::Left, Edge::Press));
+        let release = Event::new(EventKind::Key(Key::KeyA, Edge::Release));
+        let wheel = Event::new(EventKind::Wheel {
+            delta_x: 0,
+            delta_y: 1,
+        });
+        assert_eq!(press.pressed_key(), Some(Key::KeyMouseLeft));
+        assert_eq!(release.pressed_key(), None);
+        assert_eq!(release.key(), Some(Key::KeyA));
+        assert_eq!(wheel.edge(), None);
+    }
+}
@@ -1,7 +1,17 @@
 use thiserror::Error;
 
-#[derive(Debug, Error, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum Error {
     #[error("Provided index is greater than max index.")]
     OutOfIndex,
+    #[error("Unknown key `{0}`.")]
+    UnknownKey(String),
+    #[error("Hotkey string should not be empty.")]
+    EmptyHotkey,
+    #[error("The last bind key has no next step to delay, use `KeySet::settle` instead.")]
+    TrailingDelay,
+    #[error("Sequence deadline is shorter than a gap allowed between its steps.")]
+    DeadlineTooShort,
+    #[error("Unknown profile `{0}`.")]
+    UnknownProfile(String),
 }
@@ -1,9 +1,21 @@
-use rdev::Event;
+use std::collections::BTreeSet;
+
+use crate::{
+    event::{Edge, Event},
+    key::Key,
+};
 
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "crate::serde_impl::RawHistory"))]
 pub struct History {
     capacity: usize,
     entries: Vec<Event>,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    pressed: BTreeSet<Key>,
+    /// Index of the first entry a sequence may start at.
+    #[cfg_attr(feature = "serde", serde(skip))]
+    boundary: usize,
 }
 
 impl History {
@@ -11,13 +23,51 @@ impl History {
         Self {
             capacity,
             entries: Vec::new(),
+            pressed: BTreeSet::new(),
+            boundary: 0,
         }
     }
 
     pub fn push(&mut self, event: Event) {
+        match (event.key(), event.edge()) {
+            (Some(key), Some(Edge::Press)) => {
+                self.pressed.insert(key);
+            }
+            (Some(key), Some(Edge::Release)) => {
+                self.pressed.remove(&key);
+            }
+            _ => {}
+        }
         self.entries.push(event);
     }
 
+    /// Keys and mouse buttons currently held down.
+    pub fn pressed(&self) -> &BTreeSet<Key> {
+        &self.pressed
+    }
+
+    /// Keys held down right after the entry at `index`, derived from the
+    /// current state by undoing the later entries.
+    pub fn pressed_at(&self, index: usize) -> BTreeSet<Key> {
+        let mut pressed = self.pressed.clone();
+        for event in self.entries.iter().skip(index + 1).rev() {
+            match (event.key(), event.edge()) {
+                (Some(key), Some(Edge::Press)) => {
+                    pressed.remove(&key);
+                }
+                (Some(key), Some(Edge::Release)) => {
+                    pressed.insert(key);
+                }
+                _ => {}
+            }
+        }
+        pressed
+    }
+
+    pub fn is_pressed(&self, key: Key) -> bool {
+        self.pressed.iter().any(|pressed| key.matches(*pressed))
+    }
+
     pub fn last_n(&self, n: usize) -> &[Event] {
         &self.entries[self.entries.len().saturating_sub(n)..]
     }
@@ -26,12 +76,42 @@ impl History {
         self.entries.last()
     }
 
+    pub fn last_press(&self) -> Option<&Event> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|event| event.pressed_key().is_some())
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Event> {
+        self.entries.iter()
+    }
+
     pub fn len(&self) -> usize {
         self.entries.len()
     }
 
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Starts sequences over from the next event. Past events are kept for
+    /// context such as [`crate::key::KeySet::not_after`] or tap counts, but
+    /// no sequence can continue from them.
+    pub fn reset(&mut self) {
+        self.boundary = self.entries.len();
+    }
+
+    /// Index of the first entry a sequence may start at, moved by
+    /// [`History::reset`].
+    pub fn boundary(&self) -> usize {
+        self.boundary
+    }
+
     pub fn clean(&mut self) {
+        let removed = self.entries.len().saturating_sub(self.capacity);
         self.entries = self.last_n(self.capacity).to_vec();
+        self.boundary = self.boundary.saturating_sub(removed);
     }
 
     pub fn matches(&self, events: &[Event]) -> bool {
@@ -42,43 +122,31 @@ impl History {
 
 impl From<Vec<Event>> for History {
     fn from(events: Vec<Event>) -> Self {
-        Self {
-            capacity: 256,
-            entries: events,
+        let mut history = History::new(256);
+        for event in events {
+            history.push(event);
         }
+        history
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::time::SystemTime;
-
     use super::*;
+    use crate::event::{Button, EventKind};
 
     #[test]
     fn test_history_record() {
         let mut history = History::new(1024);
-        history.push(Event {
-            time: SystemTime::now(),
-            name: None,
-            event_type: rdev::EventType::KeyPress(rdev::Key::ControlLeft),
-        });
-        history.push(Event {
-            time: SystemTime::now(),
-            name: None,
-            event_type: rdev::EventType::KeyRelease(rdev::Key::ControlLeft),
-        });
+        history.push(Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Press)));
+        history.push(Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Release)));
         assert_eq!(history.entries.len(), 2);
     }
 
     #[test]
     fn test_history_last_slice() {
         let mut history = History::new(1024);
-        let event = Event {
-            time: SystemTime::now(),
-            name: None,
-            event_type: rdev::EventType::KeyPress(rdev::Key::ControlLeft),
-        };
+        let event = Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Press));
         for _ in 0..10 {
             history.push(event.clone());
         }
@@ -88,16 +156,8 @@ mod tests {
     #[test]
     fn test_history_last_n() {
         let mut history = History::new(1024);
-        let event = Event {
-            time: SystemTime::now(),
-            name: None,
-            event_type: rdev::EventType::KeyPress(rdev::Key::ControlLeft),
-        };
-        let last = Event {
-            time: SystemTime::now(),
-            name: None,
-            event_type: rdev::EventType::KeyPress(rdev::Key::KeyA),
-        };
+        let event = Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Press));
+        let last = Event::new(EventKind::Key(Key::KeyA, Edge::Press));
         for _ in 0..10 {
             history.push(event.clone());
         }
@@ -105,19 +165,78 @@ mod tests {
         assert_eq!(history.last().unwrap(), &last);
     }
 
+    #[test]
+    fn test_history_last_press() {
+        let mut history = History::new(1024);
+        let press = Event::new(EventKind::Key(Key::KeyA, Edge::Press));
+        history.push(press.clone());
+        history.push(Event::new(EventKind::Key(Key::KeyA, Edge::Release)));
+        history.push(Event::new(EventKind::Move { x: 0.0, y: 0.0 }));
+        assert_eq!(history.last_press(), Some(&press));
+    }
+
+    #[test]
+    fn test_history_pressed() {
+        let mut history = History::new(1024);
+        history.push(Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Press)));
+        history.push(Event::new(EventKind::Button(Button::Left, Edge::Press)));
+        history.push(Event::new(EventKind::Key(Key::KeyA, Edge::Press)));
+        history.push(Event::new(EventKind::Key(Key::KeyA, Edge::Release)));
+        assert_eq!(
+            history.pressed().iter().copied().collect::<Vec<_>>(),
+            vec![Key::KeyLeftCtrl, Key::KeyMouseLeft]
+        );
+        assert!(history.is_pressed(Key::KeyCtrl));
+        assert!(!history.is_pressed(Key::KeyA));
+
+        history.clean();
+        assert!(history.is_pressed(Key::KeyLeftCtrl));
+
+        history.reset();
+        assert_eq!(history.boundary(), history.len());
+        assert!(history.is_pressed(Key::KeyLeftCtrl));
+    }
+
+    #[test]
+    fn test_history_reset() {
+        let mut history = History::new(2);
+        let event = Event::new(EventKind::Key(Key::KeyA, Edge::Press));
+        for _ in 0..3 {
+            history.push(event.clone());
+        }
+        history.reset();
+        history.push(event.clone());
+        assert_eq!(history.boundary(), 3);
+
+        // Cleaning keeps the boundary on the same event.
+        history.clean();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.boundary(), 1);
+    }
+
+    #[test]
+    fn test_history_pressed_at() {
+        let history = History::from(vec![
+            Event::new(EventKind::Key(Key::KeyLeftShift, Edge::Press)),
+            Event::new(EventKind::Key(Key::KeyA, Edge::Press)),
+            Event::new(EventKind::Key(Key::KeyLeftShift, Edge::Release)),
+            Event::new(EventKind::Key(Key::KeyB, Edge::Press)),
+        ]);
+        assert_eq!(
+            history.pressed_at(1).into_iter().collect::<Vec<_>>(),
+            vec![Key::KeyA, Key::KeyLeftShift]
+        );
+        assert_eq!(
+            history.pressed_at(3).into_iter().collect::<Vec<_>>(),
+            vec![Key::KeyA, Key::KeyB]
+        );
+    }
+
     #[test]
     fn test_history_from_vec() {
         let events = vec![
-            Event {
-                time: SystemTime::now(),
-                name: None,
-                event_type: rdev::EventType::KeyPress(rdev::Key::ControlLeft),
-            },
-            Event {
-                time: SystemTime::now(),
-                name: None,
-                event_type: rdev::EventType::KeyRelease(rdev::Key::ControlLeft),
-            },
+            Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Press)),
+            Event::new(EventKind::Key(Key::KeyLeftCtrl, Edge::Release)),
         ];
         let history = History::from(events.clone());
         assert_eq!(history.entries.len(), 2);
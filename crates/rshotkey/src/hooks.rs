@@ -1,123 +1,1556 @@
-use std::sync::Arc;
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime},
+};
 
 use anyhow::Result;
-use futures::TryFutureExt;
-use rdev::Event;
-use tokio::sync::RwLock;
+use futures::{future::join_all, FutureExt};
+use tokio::sync::{Notify, RwLock};
 
-use crate::{history::History, key::KeySet, listener::Hook};
+use crate::{
+    event::Event,
+    history::History,
+    key::{BindKey, Capture, ChordOrder, CountPrefix, Key, KeySet, ModifierPolicy},
+    listener::{Binding, HookContext, HookResult, Listener},
+    mode::Modes,
+    notification::{CancelReason, Notification},
+    profile::Settings,
+    resolver::{find_pending, resolve, Claim, Resolution, CONTINUATION_TIMEOUT},
+    trigger::{
+        match_hold_start, match_tap_alone, match_tap_count, resolve_dual_role,
+        resolve_dual_role_timeout, still_held, tap_run_extended, DualRole, Role, Trigger,
+    },
+};
 
-pub async fn hook_keyset(
-    key_set: KeySet,
-    history_arc: Arc<RwLock<History>>,
-    hook: Hook,
-) -> Result<()> {
-    let mut matched = true;
-    let history = history_arc.read().await.clone();
+/// Checks whether the latest presses in `history` complete `key_set`,
+/// ignoring its settle time.
+///
+/// Only key and mouse button presses take part in matching, releases and
+/// pointer events are kept in history but skipped here.
+pub fn match_keyset(key_set: &KeySet, history: &History) -> bool {
+    capture_keyset(key_set, history).is_some()
+}
+
+/// Like [`match_keyset`], returning the concrete keys pressed for key
+/// classes and generic modifiers on success.
+pub fn capture_keyset(key_set: &KeySet, history: &History) -> Option<Vec<Capture>> {
     let key_set_length = key_set.len();
-    if key_set_length > history.len() {
-        return Ok(());
+    if key_set_length == 0 {
+        return None;
     }
 
-    let mut history_to_match = history.last_n(key_set_length).to_vec();
+    // One extra press is kept to check `not_after`.
+    let mut presses: Vec<(usize, Key, SystemTime)> = history
+        .iter()
+        .enumerate()
+        .rev()
+        .filter_map(|(idx, event)| event.pressed_key().map(|key| (idx, key, event.time)))
+        .take(key_set_length + 1)
+        .collect();
+    if presses.len() < key_set_length {
+        return None;
+    }
+    presses.reverse();
+    if presses.len() > key_set_length {
+        let (_, before, _) = presses.remove(0);
+        if key_set.not_after.iter().any(|key| key.matches(before)) {
+            return None;
+        }
+    }
+    // A cancelled sequence cannot be picked up again.
+    if presses[0].0 < history.boundary() {
+        return None;
+    }
+    let history_to_match: Vec<(Key, SystemTime)> =
+        presses.iter().map(|(_, key, time)| (*key, *time)).collect();
 
-    let last_key = key_set.last().unwrap();
-    let last_delay = last_key.delay_time;
+    if !key_set.deadline.is_zero() {
+        if let (Some((_, first)), Some((_, last))) =
+            (history_to_match.first(), history_to_match.last())
+        {
+            if last.duration_since(*first).unwrap_or_default() > key_set.deadline {
+                return None;
+            }
+        }
+    }
 
-    let last_history = history.last().unwrap();
-    let last_time = last_history.time;
+    let mut captures = vec![];
+    let mut offset = 0;
+    for bind_key in &key_set.bind_keys {
+        if bind_key.is_empty() {
+            return None;
+        }
+        let checks = &history_to_match[offset..offset + bind_key.len()];
+        offset += bind_key.len();
 
-    for mut bind_key in key_set {
-        let to_idx = bind_key.len();
-        let mut checks = history_to_match[..to_idx].to_vec();
-        history_to_match = history_to_match[to_idx..].to_vec();
+        let assigned = match_bind_key(bind_key, checks)?;
+        if !match_combo(bind_key, checks)
+            || !match_without(bind_key, history, presses[offset - 1].0)
+        {
+            return None;
+        }
+        captures.extend(
+            assigned
+                .into_iter()
+                .zip(checks)
+                .filter(|(class, (key, _))| class != key)
+                .map(|(class, (key, _))| Capture { class, key: *key }),
+        );
 
-        let last_check = if checks.is_empty() {
-            matched = false;
-            break;
-        } else {
-            checks.last().unwrap().to_owned()
-        };
-        while !checks.is_empty() {
-            let mut found = false;
-            let mut check_idx = 0;
-            while check_idx < checks.len() {
-                let mut key_idx = 0;
-                while key_idx < bind_key.len() {
-                    if bind_key.keys[key_idx] == checks[check_idx].event_type.into() {
-                        found = true;
-                        checks.remove(check_idx);
-                        bind_key.keys.remove(key_idx);
-                        break;
-                    }
-                    key_idx += 1;
+        if !bind_key.delay_time.is_zero() {
+            if let (Some((_, last_check)), Some((_, next))) =
+                (checks.last(), history_to_match.get(offset))
+            {
+                let duration = next.duration_since(*last_check).unwrap_or_default();
+                if duration > bind_key.delay_time {
+                    return None;
+                }
+            }
+        }
+    }
+    match_modifiers(key_set, history).then_some(captures)
+}
+
+fn match_modifiers(key_set: &KeySet, history: &History) -> bool {
+    match (key_set.modifier_policy, key_set.last()) {
+        (ModifierPolicy::Exact, Some(last)) => history
+            .pressed()
+            .iter()
+            .filter(|pressed| pressed.is_modifier())
+            .all(|pressed| last.keys().iter().any(|key| key.matches(*pressed))),
+        _ => true,
+    }
+}
+
+/// Returns the bound key each press was matched against.
+fn match_bind_key(bind_key: &BindKey, checks: &[(Key, SystemTime)]) -> Option<Vec<Key>> {
+    match bind_key.chord_order() {
+        ChordOrder::Unordered => match_unordered(bind_key.keys(), checks),
+        ChordOrder::Ordered => bind_key
+            .keys()
+            .iter()
+            .zip(checks)
+            .all(|(bind, (key, _))| bind.matches(*key))
+            .then(|| bind_key.keys().to_vec()),
+        ChordOrder::ModifiersFirst => {
+            let modifiers = bind_key.keys().partition_point(|key| key.is_modifier());
+            let mut assigned =
+                match_unordered(&bind_key.keys()[..modifiers], &checks[..modifiers])?;
+            assigned.extend(match_unordered(
+                &bind_key.keys()[modifiers..],
+                &checks[modifiers..],
+            )?);
+            Some(assigned)
+        }
+    }
+}
+
+fn match_without(bind_key: &BindKey, history: &History, index: usize) -> bool {
+    if bind_key.without.is_empty() {
+        return true;
+    }
+    let pressed = history.pressed_at(index);
+    !bind_key
+        .without
+        .iter()
+        .any(|key| pressed.iter().any(|held| key.matches(*held)))
+}
+
+fn match_combo(bind_key: &BindKey, checks: &[(Key, SystemTime)]) -> bool {
+    if bind_key.combo_time.is_zero() {
+        return true;
+    }
+    let times = checks.iter().map(|(_, time)| *time);
+    match (times.clone().min(), times.max()) {
+        (Some(first), Some(last)) => {
+            last.duration_since(first).unwrap_or_default() <= bind_key.combo_time
+        }
+        _ => true,
+    }
+}
+
+fn match_unordered(bind_keys: &[Key], checks: &[(Key, SystemTime)]) -> Option<Vec<Key>> {
+    if bind_keys.len() != checks.len() {
+        return None;
+    }
+    let mut used = vec![false; bind_keys.len()];
+    let mut assigned = Vec::with_capacity(checks.len());
+    assign_unordered(bind_keys, checks, &mut used, &mut assigned).then_some(assigned)
+}
+
+/// Backtracking assignment of presses to distinct bound keys, needed once
+/// classes overlap each other, e.g. `AnyLetter+AnyKey` pressed as `1` `A`.
+pub(crate) fn assign_unordered(
+    bind_keys: &[Key],
+    checks: &[(Key, SystemTime)],
+    used: &mut [bool],
+    assigned: &mut Vec<Key>,
+) -> bool {
+    let Some(((key, _), rest)) = checks.split_first() else {
+        return true;
+    };
+    let mut candidates: Vec<usize> = (0..bind_keys.len())
+        .filter(|idx| !used[*idx] && bind_keys[*idx].matches(*key))
+        .collect();
+    // Prefer an exact key so classes stay free for the other presses.
+    candidates.sort_by_key(|idx| bind_keys[*idx] != *key);
+    for idx in candidates {
+        used[idx] = true;
+        assigned.push(bind_keys[idx]);
+        if assign_unordered(bind_keys, rest, used, assigned) {
+            return true;
+        }
+        used[idx] = false;
+        assigned.pop();
+    }
+    false
+}
+
+pub async fn hook_keyset(
+    key_set: KeySet,
+    history: History,
+    history_arc: Arc<RwLock<History>>,
+    hook: impl Fn(HookContext) -> HookResult + Send + Sync,
+) -> Result<()> {
+    let events = Arc::new(Notify::new());
+    hook_keysets(vec![(key_set, hook)], vec![], history, history_arc, events).await
+}
+
+/// Fires the hooks of the one key set [`resolve`] settles on for the latest
+/// press, waiting first if a longer binding or one of the `timed` triggers
+/// may still continue.
+///
+/// Like the other `hook_*` functions, it matches `history` as it was right
+/// after the event, later events are only read from `history_arc` to see
+/// how the trigger went on. `events` wakes the wait on each of them, so a
+/// hold released early lets the key set fire right away.
+pub async fn hook_keysets<F>(
+    bindings: Vec<(KeySet, F)>,
+    timed: Vec<Trigger>,
+    history: History,
+    history_arc: Arc<RwLock<History>>,
+    events: Arc<Notify>,
+) -> Result<()>
+where
+    F: Fn(HookContext) -> HookResult + Send + Sync,
+{
+    let last_time = match history.last().filter(|event| event.pressed_key().is_some()) {
+        Some(last_press) => last_press.time,
+        None => return Ok(()),
+    };
+
+    let key_sets: Vec<KeySet> = bindings
+        .iter()
+        .map(|(key_set, _)| key_set.clone())
+        .collect();
+    let (key_set, captures) = match resolve(&key_sets, &timed, &history) {
+        Resolution::Idle => return Ok(()),
+        Resolution::Fire { key_set, captures } => (key_set, captures),
+        Resolution::Wait {
+            key_set,
+            captures,
+            timeout,
+            claims,
+        } => {
+            let elapsed = SystemTime::now()
+                .duration_since(last_time)
+                .unwrap_or_default();
+            let pressed_at = tokio::time::Instant::now() - elapsed;
+            loop {
+                // Registered before reading, so no event slips in between.
+                let changed = events.notified();
+                let now_history = history_arc.read().await;
+                let now_last_time = now_history.last_press().map(|event| event.time);
+                // A reset while waiting, by the sequence timeout for one,
+                // cancels the presses up to it.
+                let reset = now_history
+                    .iter()
+                    .rposition(|event| event.pressed_key().is_some())
+                    .is_some_and(|last_press| last_press < now_history.boundary());
+                if now_last_time != Some(last_time) || reset {
+                    return Ok(());
                 }
-                if found {
+                let wait = claims
+                    .iter()
+                    .filter(|claim| claim.applies(&now_history))
+                    .filter_map(Claim::hold)
+                    .fold(timeout, Duration::max);
+                if pressed_at.elapsed() >= wait {
+                    if claims.iter().any(|claim| claim.applies(&now_history)) {
+                        return Ok(());
+                    }
                     break;
                 }
-                check_idx += 1;
-            }
-            if !found {
-                matched = false;
-                break;
+                drop(now_history);
+                tokio::select! {
+                    _ = tokio::time::sleep_until(pressed_at + wait) => {}
+                    _ = changed => {}
+                }
             }
+            (key_set, captures)
         }
+    };
+
+    let count = match key_set.count_prefix {
+        CountPrefix::Off => None,
+        CountPrefix::Pass | CountPrefix::Repeat => count_prefix(&key_set, &history),
+    };
+    let times = match key_set.count_prefix {
+        CountPrefix::Repeat => count.unwrap_or(1),
+        _ => 1,
+    };
+    let fired = bindings
+        .into_iter()
+        .filter(|(registered, _)| *registered == key_set)
+        .map(|(_, hook)| {
+            let context = HookContext {
+                trigger: key_set.clone().into(),
+                captures: captures.clone(),
+                count,
+            };
+            async move {
+                for _ in 0..times {
+                    hook(context.clone()).await?;
+                }
+                Ok::<_, anyhow::Error>(())
+            }
+        });
+    // An error in one hook does not cut the others short.
+    join_all(fired).await.into_iter().collect()
+}
 
-        if !bind_key.delay_time.is_zero() && !history_to_match.is_empty() {
-            let next_time = history_to_match[0].time;
-            let duration = next_time.duration_since(last_check.time)?;
-            if duration > bind_key.delay_time {
-                matched = false;
-                break;
+/// The number typed right before the presses matching `key_set`, leading
+/// zeros skipped as Vim does, at most [`KeySet::max_count`].
+///
+/// Digits count like a step of the sequence: each has to be followed by the
+/// next press within the gap of the first step, or [`CONTINUATION_TIMEOUT`]
+/// if it sets none.
+pub fn count_prefix(key_set: &KeySet, history: &History) -> Option<u32> {
+    let gap = key_set
+        .bind_keys
+        .first()
+        .map(|bind_key| bind_key.delay_time)
+        .filter(|delay| !delay.is_zero())
+        .unwrap_or(CONTINUATION_TIMEOUT);
+    let mut presses = history
+        .iter()
+        .rev()
+        .filter_map(|event| event.pressed_key().map(|key| (key, event.time)))
+        .skip(key_set.len().saturating_sub(1));
+    let (_, mut next) = presses.next()?;
+    let mut digits = vec![];
+    for (key, time) in presses {
+        match key.digit() {
+            Some(digit) if next.duration_since(time).unwrap_or_default() <= gap => {
+                digits.push(digit);
+                next = time;
             }
+            _ => break,
         }
     }
-    if matched {
-        let now = std::time::SystemTime::now();
-        if !last_delay.is_zero() {
-            let elapsed = now.duration_since(last_time)?;
-            if elapsed < last_delay {
-                tokio::time::sleep(last_delay - elapsed).await;
-            }
-            let now_last_time = history_arc.read().await.last().unwrap().time;
-            if now_last_time != last_history.time {
-                matched = false;
-            }
-        };
-        if matched {
-            hook().await?;
+    digits
+        .into_iter()
+        .rev()
+        .skip_while(|digit| *digit == 0)
+        .fold(None, |count, digit| {
+            Some(
+                count
+                    .unwrap_or(0u32)
+                    .saturating_mul(10)
+                    .saturating_add(digit),
+            )
+        })
+        .map(|count| count.min(key_set.max_count))
+}
+
+pub async fn hook_tap_alone(
+    key: Key,
+    max_hold: Duration,
+    history: History,
+    hook: impl Fn(HookContext) -> HookResult + Send + Sync,
+) -> Result<()> {
+    let capture = match match_tap_alone(key, max_hold, &history) {
+        Some(capture) => capture,
+        None => return Ok(()),
+    };
+    hook(HookContext {
+        trigger: Trigger::tap_alone(key, max_hold),
+        captures: vec![capture],
+        count: None,
+    })
+    .await
+}
+
+pub async fn hook_hold(
+    key: Key,
+    duration: Duration,
+    repeat: Duration,
+    history: History,
+    history_arc: Arc<RwLock<History>>,
+    hook: impl Fn(HookContext) -> HookResult + Send + Sync,
+) -> Result<()> {
+    let (pressed, since) = match match_hold_start(key, &history) {
+        Some(start) => start,
+        None => return Ok(()),
+    };
+
+    let elapsed = SystemTime::now().duration_since(since).unwrap_or_default();
+    let mut wait = duration.saturating_sub(elapsed);
+    loop {
+        tokio::time::sleep(wait).await;
+        if !still_held(pressed, since, &*history_arc.read().await) {
+            return Ok(());
+        }
+        hook(HookContext {
+            trigger: Trigger::hold_repeat(key, duration, repeat),
+            captures: vec![Capture {
+                class: key,
+                key: pressed,
+            }],
+            count: None,
+        })
+        .await?;
+        if repeat.is_zero() {
+            return Ok(());
         }
+        wait = repeat;
+    }
+}
+
+pub async fn hook_dual_role(
+    dual_role: DualRole,
+    role: Role,
+    history: History,
+    history_arc: Arc<RwLock<History>>,
+    hook: impl Fn(HookContext) -> HookResult + Send + Sync,
+) -> Result<()> {
+    let capture = if let Some((resolved, capture)) = resolve_dual_role(&dual_role, &history) {
+        (resolved == role).then_some(capture)
+    } else if let (Role::Hold, Some((pressed, since))) =
+        (role, match_hold_start(dual_role.key, &history))
+    {
+        let elapsed = SystemTime::now().duration_since(since).unwrap_or_default();
+        tokio::time::sleep(dual_role.tapping_term.saturating_sub(elapsed)).await;
+        resolve_dual_role_timeout(&dual_role, pressed, since, &*history_arc.read().await).then_some(
+            Capture {
+                class: dual_role.key,
+                key: pressed,
+            },
+        )
+    } else {
+        None
     };
-    Ok(())
+
+    match capture {
+        Some(capture) => {
+            hook(HookContext {
+                trigger: Trigger::DualRole(dual_role, role),
+                captures: vec![capture],
+                count: None,
+            })
+            .await
+        }
+        None => Ok(()),
+    }
 }
 
-pub async fn hook(
-    history: Arc<RwLock<History>>,
-    hooks_arc: Arc<RwLock<Vec<(KeySet, Hook)>>>,
+pub async fn hook_tap_count(
+    key: Key,
+    count: usize,
+    within: Duration,
+    history: History,
+    history_arc: Arc<RwLock<History>>,
+    hook: impl Fn(HookContext) -> HookResult + Send + Sync,
 ) -> Result<()> {
-    let hooks = hooks_arc.read().await.clone();
-    for (key_set, hook) in hooks {
-        hook_keyset(key_set, Arc::clone(&history), hook).await?;
+    let (pressed, since) = match match_tap_count(key, count, within, &history) {
+        Some(tap) => tap,
+        None => return Ok(()),
+    };
+
+    let elapsed = SystemTime::now().duration_since(since).unwrap_or_default();
+    tokio::time::sleep(within.saturating_sub(elapsed)).await;
+    if tap_run_extended(pressed, since, &*history_arc.read().await) {
+        return Ok(());
+    }
+    hook(HookContext {
+        trigger: Trigger::tap_count(key, count, within),
+        captures: vec![Capture {
+            class: key,
+            key: pressed,
+        }],
+        count: None,
+    })
+    .await
+}
+
+/// Wraps the hook of `binding` to apply its mode change first, skipping it
+/// if the listener got paused while the trigger was waiting.
+fn fire(
+    binding: &Binding,
+    modes: Modes,
+    paused: Arc<AtomicBool>,
+) -> impl Fn(HookContext) -> HookResult + Send + Sync {
+    let (hook, mode_change) = (binding.hook, binding.mode_change.clone());
+    move |context| {
+        let (mode_change, modes, paused) = (mode_change.clone(), modes.clone(), paused.clone());
+        async move {
+            if paused.load(Ordering::SeqCst) {
+                return Ok(());
+            }
+            if let Some(mode_change) = mode_change {
+                modes.apply(&mode_change).await;
+            }
+            hook(context).await
+        }
+        .boxed()
     }
-    Ok(())
 }
 
-pub async fn handle_event(
-    event: Event,
+/// Runs the triggers of `bindings` for the event `history` ends with.
+pub async fn hook(
+    history: History,
     history_arc: Arc<RwLock<History>>,
-    hooks: Arc<RwLock<Vec<(KeySet, Hook)>>>,
-    max_history: usize,
+    bindings: Vec<Binding>,
+    modes: Modes,
+    paused: Arc<AtomicBool>,
+    events: Arc<Notify>,
 ) {
-    let mut history = history_arc.write().await;
+    let mut key_sets = vec![];
+    let mut timed = vec![];
+    let mut pending = vec![];
+    for binding in bindings {
+        if !matches!(binding.trigger, Trigger::KeySet(_)) {
+            timed.push(binding.trigger.clone());
+        }
+        let (history, history_arc) = (history.clone(), Arc::clone(&history_arc));
+        let hook = fire(&binding, modes.clone(), Arc::clone(&paused));
+        match binding.trigger {
+            Trigger::KeySet(key_set) => key_sets.push((key_set, hook)),
+            Trigger::TapAlone { key, max_hold } => {
+                pending.push(hook_tap_alone(key, max_hold, history, hook).boxed())
+            }
+            Trigger::Hold {
+                key,
+                duration,
+                repeat,
+            } => pending.push(hook_hold(key, duration, repeat, history, history_arc, hook).boxed()),
+            Trigger::DualRole(dual_role, role) => {
+                pending.push(hook_dual_role(dual_role, role, history, history_arc, hook).boxed())
+            }
+            Trigger::TapCount { key, count, within } => {
+                pending.push(hook_tap_count(key, count, within, history, history_arc, hook).boxed())
+            }
+        }
+    }
+    // Key sets are resolved together, and against the timed triggers, so only
+    // one of them fires.
+    pending.push(hook_keysets(key_sets, timed, history, Arc::clone(&history_arc), events).boxed());
+    // Timed triggers wait on their own, so one of them must neither hold back
+    // nor, failing, cancel the rest.
+    for result in join_all(pending).await {
+        if let Err(e) = result {
+            eprintln!("Hook failed: {:?}", e);
+        }
+    }
+}
+
+pub async fn handle_event(event: Event, listener: Listener) {
+    let event_key = event.pressed_key();
+    let is_press = event_key.is_some();
+    let mut history = listener.history.write().await;
     history.push(event);
 
-    if history.len() > max_history {
+    if history.len() > listener.max_history {
         history.clean();
     }
-
+    let kill_switch = is_press
+        && listener
+            .kill_switch_key_set()
+            .is_some_and(|kill_switch| match_keyset(&kill_switch, &history));
+    // Triggers match the history as of this event, whatever arrives next.
+    let snapshot = history.clone();
     drop(history);
+    listener.events.notify_waiters();
+
+    if kill_switch {
+        if listener.is_paused() {
+            listener.resume().await;
+        } else {
+            listener.pause().await;
+        }
+        return;
+    }
+    if listener.is_paused() {
+        return;
+    }
 
-    tokio::spawn(
-        hook(Arc::clone(&history_arc), Arc::clone(&hooks)).unwrap_or_else(|e| eprint!("{}", e)),
+    // One read of the loaded profile serves the whole event.
+    let profile = listener.loaded_profile();
+    let bindings = listener.active_bindings_of(&profile).await;
+    if is_press {
+        let cancels = match (profile.settings.cancel_key, event_key) {
+            (Some(cancel_key), Some(key)) => cancel_key.matches(key),
+            _ => false,
+        };
+        if cancels && cancel_pending(&listener, CancelReason::CancelKey).await {
+            return;
+        }
+        track_pending(&listener, &bindings, &profile.settings).await;
+    }
+
+    tokio::spawn(hook(
+        snapshot,
+        Arc::clone(&listener.history),
+        bindings,
+        listener.modes.clone(),
+        Arc::clone(&listener.paused),
+        Arc::clone(&listener.events),
+    ));
+}
+
+/// Updates the sequence in progress after a press against the active
+/// `bindings` and notifies subscribers.
+pub async fn track_pending(listener: &Listener, bindings: &[Binding], settings: &Settings) {
+    let key_sets: Vec<KeySet> = bindings
+        .iter()
+        .filter_map(|binding| binding.trigger.as_key_set())
+        .collect();
+    let pending = find_pending(
+        &key_sets,
+        &*listener.history.read().await,
+        SystemTime::now(),
     );
+
+    let mut current = listener.pending.write().await;
+    let was_pending = current.is_some();
+    *current = pending.clone();
+    drop(current);
+
+    match pending {
+        Some(pending) => {
+            listener
+                .notifier
+                .notify(Notification::Pending(pending.clone()));
+
+            let mut expires_at = pending.expires_at;
+            let mut timed_out = false;
+            if let Some(first) = pending.events.first() {
+                let sequence_timeout = settings.sequence_timeout;
+                let deadline = first.time + sequence_timeout;
+                if !sequence_timeout.is_zero() && deadline < expires_at {
+                    expires_at = deadline;
+                    timed_out = true;
+                }
+            }
+
+            let listener = listener.clone();
+            tokio::spawn(async move {
+                let timeout = expires_at
+                    .duration_since(SystemTime::now())
+                    .unwrap_or_default();
+                tokio::time::sleep(timeout).await;
+                if listener.pending.read().await.as_ref() != Some(&pending) {
+                    return;
+                }
+                if timed_out {
+                    cancel_pending(&listener, CancelReason::Timeout).await;
+                } else {
+                    *listener.pending.write().await = None;
+                    listener.notifier.notify(Notification::PendingCleared);
+                }
+            });
+        }
+        None if was_pending => {
+            listener.notifier.notify(Notification::PendingCleared);
+        }
+        None => {}
+    }
+}
+
+/// Resets the sequence in progress and notifies subscribers, returning
+/// whether there was one.
+pub async fn cancel_pending(listener: &Listener, reason: CancelReason) -> bool {
+    let Some(pending) = listener.pending.write().await.take() else {
+        return false;
+    };
+    listener.history.write().await.reset();
+    listener
+        .notifier
+        .notify(Notification::SequenceCancelled(pending, reason));
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::Mutex,
+        time::{Duration, SystemTime},
+    };
+
+    use futures::FutureExt;
+    use proptest::prelude::*;
+    use rdev::EventType;
+
+    use super::*;
+    use crate::{event::Edge, key::DEFAULT_MAX_COUNT, listener::HookResult};
+
+    const POOL: [rdev::Key; 4] = [
+        rdev::Key::KeyA,
+        rdev::Key::KeyB,
+        rdev::Key::ControlLeft,
+        rdev::Key::Num1,
+    ];
+
+    fn event_at(time: SystemTime, event_type: EventType) -> Event {
+        Event::new(event_type.into()).at(time)
+    }
+
+    fn base_time() -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000)
+    }
+
+    fn any_key() -> impl Strategy<Value = rdev::Key> {
+        prop::sample::select(POOL.to_vec())
+    }
+
+    fn any_event_type() -> impl Strategy<Value = EventType> {
+        prop_oneof![
+            4 => any_key().prop_map(EventType::KeyPress),
+            1 => any_key().prop_map(EventType::KeyRelease),
+            1 => Just(EventType::ButtonPress(rdev::Button::Left)),
+            1 => Just(EventType::MouseMove { x: 0.0, y: 0.0 }),
+        ]
+    }
+
+    fn any_bind_key() -> impl Strategy<Value = BindKey> {
+        (
+            prop::collection::vec(any_key(), 0..3),
+            prop_oneof![Just(0u64), 1..500u64],
+        )
+            .prop_map(|(keys, delay)| {
+                let keys = keys
+                    .into_iter()
+                    .map(EventType::KeyPress)
+                    .collect::<Vec<_>>();
+                BindKey::from(keys).delay(Duration::from_millis(delay))
+            })
+    }
+
+    fn any_key_set() -> impl Strategy<Value = KeySet> {
+        prop::collection::vec(any_bind_key(), 0..4).prop_map(KeySet::new)
+    }
+
+    fn any_history() -> impl Strategy<Value = History> {
+        prop::collection::vec((any_event_type(), 0..1_000u64, any::<bool>()), 0..12).prop_map(
+            |entries| {
+                let mut history = History::new(256);
+                let mut time = base_time();
+                for (event_type, gap, backwards) in entries {
+                    // Wall clocks may jump backwards, the matcher must cope with it.
+                    time = if backwards {
+                        time - Duration::from_millis(gap)
+                    } else {
+                        time + Duration::from_millis(gap)
+                    };
+                    history.push(event_at(time, event_type));
+                }
+                history
+            },
+        )
+    }
+
+    /// A non-empty key set of key presses only, paired with the same presses
+    /// shuffled inside every bind key.
+    fn key_set_with_presses() -> impl Strategy<Value = (KeySet, Vec<Vec<rdev::Key>>)> {
+        prop::collection::vec(
+            (
+                prop::sample::subsequence(POOL.to_vec(), 1..3),
+                prop_oneof![Just(0u64), 1..500u64],
+            ),
+            1..4,
+        )
+        .prop_flat_map(|steps| {
+            let shuffled = steps
+                .iter()
+                .map(|(keys, _)| Just(keys.clone()).prop_shuffle())
+                .collect::<Vec<_>>();
+            (Just(steps), shuffled)
+        })
+        .prop_map(|(steps, shuffled)| {
+            let key_set = KeySet::new(
+                steps
+                    .into_iter()
+                    .map(|(keys, delay)| {
+                        BindKey::from(
+                            keys.into_iter()
+                                .map(EventType::KeyPress)
+                                .collect::<Vec<_>>(),
+                        )
+                        .delay(Duration::from_millis(delay))
+                    })
+                    .collect(),
+            );
+            (key_set, shuffled)
+        })
+    }
+
+    /// Appends `steps` to `history`, separating bind keys by `gap(step)`.
+    fn push_steps(
+        history: &mut History,
+        steps: &[Vec<rdev::Key>],
+        gap: impl Fn(usize) -> Duration,
+    ) {
+        let mut time = history
+            .last()
+            .map(|event| event.time)
+            .unwrap_or(base_time());
+        for (idx, step) in steps.iter().enumerate() {
+            if idx > 0 {
+                time += gap(idx - 1);
+            }
+            for key in step {
+                history.push(event_at(time, EventType::KeyPress(*key)));
+            }
+        }
+    }
+
+    /// Straightforward reference implementation of the matcher.
+    fn oracle(key_set: &KeySet, history: &History) -> bool {
+        let presses: Vec<&Event> = history
+            .iter()
+            .filter(|event| event.edge() == Some(Edge::Press))
+            .collect();
+        let total = key_set.len();
+        if total == 0 || total > presses.len() {
+            return false;
+        }
+        let events = &presses[presses.len() - total..];
+        let mut offset = 0;
+        for bind_key in &key_set.bind_keys {
+            let chunk: Vec<Key> = events[offset..offset + bind_key.len()]
+                .iter()
+                .filter_map(|event| event.key())
+                .collect();
+            offset += bind_key.len();
+            let count = |keys: &[Key], key: &Key| keys.iter().filter(|k| *k == key).count();
+            if bind_key.is_empty()
+                || !chunk
+                    .iter()
+                    .all(|key| count(&chunk, key) == count(bind_key.keys(), key))
+            {
+                return false;
+            }
+            if !bind_key.delay_time.is_zero() && offset < events.len() {
+                let gap = events[offset]
+                    .time
+                    .duration_since(events[offset - 1].time)
+                    .unwrap_or(Duration::ZERO);
+                if gap > bind_key.delay_time {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    proptest! {
+        #[test]
+        fn test_match_keyset_never_panics(key_set in any_key_set(), history in any_history()) {
+            match_keyset(&key_set, &history);
+        }
+
+        #[test]
+        fn test_match_keyset_agrees_with_oracle(key_set in any_key_set(), history in any_history()) {
+            prop_assert_eq!(match_keyset(&key_set, &history), oracle(&key_set, &history));
+        }
+
+        #[test]
+        fn test_match_keyset_finds_pattern(
+            (key_set, steps) in key_set_with_presses(),
+            mut history in any_history(),
+        ) {
+            let delays: Vec<Duration> = key_set.bind_keys.iter().map(|key| key.delay_time).collect();
+            push_steps(&mut history, &steps, |idx| delays[idx]);
+            prop_assert!(match_keyset(&key_set, &history));
+        }
+
+        #[test]
+        fn test_match_keyset_respects_delay(
+            (key_set, steps) in key_set_with_presses(),
+            mut history in any_history(),
+            late in any::<prop::sample::Index>(),
+        ) {
+            let delays: Vec<Duration> = key_set.bind_keys.iter().map(|key| key.delay_time).collect();
+            let bounded: Vec<usize> = (0..delays.len() - 1)
+                .filter(|idx| !delays[*idx].is_zero())
+                .collect();
+            prop_assume!(!bounded.is_empty());
+            let late = bounded[late.index(bounded.len())];
+            push_steps(&mut history, &steps, |idx| {
+                if idx == late {
+                    delays[idx] + Duration::from_millis(1)
+                } else {
+                    delays[idx]
+                }
+            });
+            prop_assert!(!match_keyset(&key_set, &history));
+        }
+    }
+
+    #[test]
+    fn test_match_keyset_empty() {
+        let mut history = History::new(16);
+        assert!(!match_keyset(&KeySet::default(), &history));
+        history.push(event_at(base_time(), EventType::KeyPress(rdev::Key::KeyA)));
+        assert!(!match_keyset(&KeySet::default(), &history));
+        assert!(!match_keyset(
+            &KeySet::new(vec![BindKey::new(vec![])]),
+            &history
+        ));
+    }
+
+    #[test]
+    fn test_match_keyset_skips_releases() {
+        let key_set = KeySet::default()
+            .bind(BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyK]))
+            .bind(BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyC]));
+        let mut history = History::new(16);
+        for event_type in [
+            EventType::KeyPress(rdev::Key::ControlLeft),
+            EventType::KeyPress(rdev::Key::KeyK),
+            EventType::KeyRelease(rdev::Key::KeyK),
+            EventType::KeyRelease(rdev::Key::ControlLeft),
+            EventType::MouseMove { x: 1.0, y: 1.0 },
+            EventType::KeyPress(rdev::Key::ControlLeft),
+            EventType::KeyPress(rdev::Key::KeyC),
+        ] {
+            history.push(event_at(base_time(), event_type));
+        }
+        assert!(match_keyset(&key_set, &history));
+    }
+
+    fn presses(keys: &[rdev::Key]) -> History {
+        let mut history = History::new(16);
+        for key in keys {
+            history.push(event_at(base_time(), EventType::KeyPress(*key)));
+        }
+        history
+    }
+
+    #[test]
+    fn test_match_keyset_chord_order() {
+        let ctrl_then_s = presses(&[rdev::Key::ControlLeft, rdev::Key::KeyS]);
+        let s_then_ctrl = presses(&[rdev::Key::KeyS, rdev::Key::ControlLeft]);
+
+        let unordered: KeySet = BindKey::new(vec![Key::KeyS, Key::KeyLeftCtrl]).into();
+        assert!(match_keyset(&unordered, &ctrl_then_s));
+        assert!(match_keyset(&unordered, &s_then_ctrl));
+
+        let ordered: KeySet = BindKey::ordered(vec![Key::KeyLeftCtrl, Key::KeyS]).into();
+        assert!(match_keyset(&ordered, &ctrl_then_s));
+        assert!(!match_keyset(&ordered, &s_then_ctrl));
+
+        let modifiers_first: KeySet = BindKey::new(vec![Key::KeyS, Key::KeyLeftCtrl])
+            .order(ChordOrder::ModifiersFirst)
+            .into();
+        assert!(match_keyset(&modifiers_first, &ctrl_then_s));
+        assert!(!match_keyset(&modifiers_first, &s_then_ctrl));
+    }
+
+    #[test]
+    fn test_match_keyset_modifiers_first_allows_any_order_after() {
+        let key_set: KeySet =
+            BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyShift, Key::KeyA, Key::KeyB])
+                .order(ChordOrder::ModifiersFirst)
+                .into();
+        let history = presses(&[
+            rdev::Key::ShiftLeft,
+            rdev::Key::ControlLeft,
+            rdev::Key::KeyB,
+            rdev::Key::KeyA,
+        ]);
+        assert!(match_keyset(&key_set, &history));
+        let history = presses(&[
+            rdev::Key::ShiftLeft,
+            rdev::Key::KeyB,
+            rdev::Key::ControlLeft,
+            rdev::Key::KeyA,
+        ]);
+        assert!(!match_keyset(&key_set, &history));
+    }
+
+    #[test]
+    fn test_match_keyset_combo() {
+        let key_set: KeySet = BindKey::new(vec![Key::KeyJ, Key::KeyK])
+            .combo(Duration::from_millis(50))
+            .into();
+        let mut history = History::new(16);
+        history.push(event_at(base_time(), EventType::KeyPress(rdev::Key::KeyK)));
+        history.push(event_at(
+            base_time() + Duration::from_millis(30),
+            EventType::KeyPress(rdev::Key::KeyJ),
+        ));
+        assert!(match_keyset(&key_set, &history));
+
+        history.push(event_at(
+            base_time() + Duration::from_millis(200),
+            EventType::KeyPress(rdev::Key::KeyK),
+        ));
+        assert!(!match_keyset(&key_set, &history));
+        let sequential: KeySet = BindKey::new(vec![Key::KeyJ, Key::KeyK]).into();
+        assert!(match_keyset(&sequential, &history));
+    }
+
+    #[test]
+    fn test_match_keyset_generic_modifier() {
+        let key_set: KeySet = "Ctrl+S".parse().unwrap();
+        assert!(match_keyset(
+            &key_set,
+            &presses(&[rdev::Key::ControlLeft, rdev::Key::KeyS])
+        ));
+        assert!(match_keyset(
+            &key_set,
+            &presses(&[rdev::Key::ControlRight, rdev::Key::KeyS])
+        ));
+
+        let left_only: KeySet = "LeftCtrl+S".parse().unwrap();
+        assert!(!match_keyset(
+            &left_only,
+            &presses(&[rdev::Key::ControlRight, rdev::Key::KeyS])
+        ));
+
+        let both: KeySet = "Ctrl+LeftCtrl".parse().unwrap();
+        assert!(match_keyset(
+            &both,
+            &presses(&[rdev::Key::ControlLeft, rdev::Key::ControlRight])
+        ));
+    }
+
+    #[test]
+    fn test_match_keyset_modifier_policy() {
+        let subset: KeySet = "Ctrl+S".parse().unwrap();
+        let exact = subset.clone().modifiers(ModifierPolicy::Exact);
+
+        let ctrl_s = presses(&[rdev::Key::ControlLeft, rdev::Key::KeyS]);
+        assert!(match_keyset(&subset, &ctrl_s));
+        assert!(match_keyset(&exact, &ctrl_s));
+
+        let ctrl_shift_s = presses(&[
+            rdev::Key::ShiftLeft,
+            rdev::Key::ControlLeft,
+            rdev::Key::KeyS,
+        ]);
+        assert!(match_keyset(&subset, &ctrl_shift_s));
+        assert!(!match_keyset(&exact, &ctrl_shift_s));
+
+        let mut released = ctrl_shift_s.clone();
+        released.push(event_at(
+            base_time(),
+            EventType::KeyRelease(rdev::Key::ShiftLeft),
+        ));
+        assert!(match_keyset(&exact, &released));
+    }
+
+    #[test]
+    fn test_capture_keyset_classes() {
+        let key_set: KeySet = "Ctrl+G AnyDigit".parse().unwrap();
+        let history = presses(&[rdev::Key::ControlRight, rdev::Key::KeyG, rdev::Key::Num7]);
+        assert_eq!(
+            capture_keyset(&key_set, &history),
+            Some(vec![
+                Capture {
+                    class: Key::KeyCtrl,
+                    key: Key::KeyRightCtrl
+                },
+                Capture {
+                    class: Key::KeyAnyDigit,
+                    key: Key::Key7
+                },
+            ])
+        );
+        let history = presses(&[rdev::Key::ControlRight, rdev::Key::KeyG, rdev::Key::KeyH]);
+        assert_eq!(capture_keyset(&key_set, &history), None);
+    }
+
+    #[test]
+    fn test_capture_keyset_overlapping_classes() {
+        let key_set: KeySet = "AnyLetter+AnyKey".parse().unwrap();
+        let history = presses(&[rdev::Key::KeyA, rdev::Key::Num1]);
+        assert_eq!(
+            capture_keyset(&key_set, &history),
+            Some(vec![
+                Capture {
+                    class: Key::KeyAnyLetter,
+                    key: Key::KeyA
+                },
+                Capture {
+                    class: Key::KeyAny,
+                    key: Key::Key1
+                },
+            ])
+        );
+        let history = presses(&[rdev::Key::Num1, rdev::Key::Num2]);
+        assert!(!match_keyset(&key_set, &history));
+    }
+
+    #[test]
+    fn test_match_keyset_without() {
+        let key_set: KeySet = BindKey::new(vec![Key::KeyAlt, Key::KeyF])
+            .without(vec![Key::KeyShift])
+            .into();
+        let history = presses(&[rdev::Key::Alt, rdev::Key::KeyF]);
+        assert!(match_keyset(&key_set, &history));
+
+        let history = presses(&[rdev::Key::ShiftRight, rdev::Key::Alt, rdev::Key::KeyF]);
+        assert!(!match_keyset(&key_set, &history));
+
+        // Releasing Shift after the chord completed does not help.
+        let mut history = presses(&[rdev::Key::ShiftRight, rdev::Key::Alt, rdev::Key::KeyF]);
+        history.push(event_at(
+            base_time(),
+            EventType::KeyRelease(rdev::Key::ShiftRight),
+        ));
+        assert!(!match_keyset(&key_set, &history));
+    }
+
+    #[test]
+    fn test_match_keyset_not_after() {
+        let key_set = KeySet::from(BindKey::new(vec![Key::KeyA])).not_after(vec![Key::KeyG]);
+        assert!(match_keyset(&key_set, &presses(&[rdev::Key::KeyA])));
+        assert!(match_keyset(
+            &key_set,
+            &presses(&[rdev::Key::KeyH, rdev::Key::KeyA])
+        ));
+        assert!(!match_keyset(
+            &key_set,
+            &presses(&[rdev::Key::KeyG, rdev::Key::KeyA])
+        ));
+    }
+
+    #[test]
+    fn test_match_keyset_deadline() {
+        let key_set = KeySet::default()
+            .bind(BindKey::new(vec![Key::KeyG]).delay(Duration::from_millis(300)))
+            .bind(BindKey::new(vec![Key::KeyG]).delay(Duration::from_millis(300)))
+            .bind(BindKey::new(vec![Key::KeyG]))
+            .deadline(Duration::from_millis(500));
+        let sequence = |millis: [u64; 3]| {
+            let mut history = History::new(16);
+            for millis in millis {
+                history.push(event_at(
+                    base_time() + Duration::from_millis(millis),
+                    EventType::KeyPress(rdev::Key::KeyG),
+                ));
+            }
+            history
+        };
+        assert!(match_keyset(&key_set, &sequence([0, 200, 400])));
+        // Every gap is within 300ms, but the whole sequence takes 550ms.
+        assert!(!match_keyset(&key_set, &sequence([0, 250, 550])));
+    }
+
+    /// A hook recording the context of every call.
+    fn recorder() -> (
+        Arc<Mutex<Vec<HookContext>>>,
+        impl Fn(HookContext) -> HookResult + Send + Sync + Clone,
+    ) {
+        let calls = Arc::new(Mutex::new(vec![]));
+        let hook = {
+            let calls = Arc::clone(&calls);
+            move |context| -> HookResult {
+                calls.lock().unwrap().push(context);
+                async move { Ok(()) }.boxed()
+            }
+        };
+        (calls, hook)
+    }
+
+    #[tokio::test]
+    async fn test_hook_keyset_calls_hook() {
+        let key_set: KeySet = BindKey::from(vec![
+            EventType::KeyPress(rdev::Key::ControlLeft),
+            EventType::KeyPress(rdev::Key::KeyD),
+        ])
+        .into();
+        let history = Arc::new(RwLock::new(History::new(16)));
+        let (calls, hook) = recorder();
+
+        let snapshot = history.read().await.clone();
+        hook_keyset(
+            key_set.clone(),
+            snapshot,
+            Arc::clone(&history),
+            hook.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 0);
+
+        let now = SystemTime::now();
+        history
+            .write()
+            .await
+            .push(event_at(now, EventType::KeyPress(rdev::Key::KeyD)));
+        history
+            .write()
+            .await
+            .push(event_at(now, EventType::KeyPress(rdev::Key::ControlLeft)));
+        let snapshot = history.read().await.clone();
+        hook_keyset(key_set, snapshot, Arc::clone(&history), hook)
+            .await
+            .unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hook_hold_repeats_until_release() {
+        let (calls, hook) = recorder();
+        let history = Arc::new(RwLock::new(History::new(16)));
+        history.write().await.push(event_at(
+            SystemTime::now(),
+            EventType::KeyPress(rdev::Key::KeyF),
+        ));
+
+        let snapshot = history.read().await.clone();
+        let holding = tokio::spawn(hook_hold(
+            Key::KeyF,
+            Duration::from_millis(50),
+            Duration::from_millis(100),
+            snapshot,
+            Arc::clone(&history),
+            hook,
+        ));
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        history.write().await.push(event_at(
+            SystemTime::now(),
+            EventType::KeyRelease(rdev::Key::KeyF),
+        ));
+        holding.await.unwrap().unwrap();
+        // Fired at 50ms and 150ms, then stopped at the release.
+        assert_eq!(calls.lock().unwrap().len(), 2);
+    }
+
+    fn noop(_: HookContext) -> HookResult {
+        async move { Ok(()) }.boxed()
+    }
+
+    fn fail(_: HookContext) -> HookResult {
+        async move { Err(anyhow::anyhow!("hook failed")) }.boxed()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hook_failure_keeps_other_triggers() {
+        let listener = Listener::default();
+        let history = History::from(vec![event_at(
+            SystemTime::now(),
+            EventType::KeyPress(rdev::Key::KeyF),
+        )]);
+        let bindings = vec![
+            Binding::new("F".parse::<KeySet>().unwrap(), fail),
+            Binding::new(Trigger::hold(Key::KeyF, Duration::from_millis(100)), noop)
+                .switch_mode(crate::mode::ModeChange::Push("held".into())),
+        ];
+        hook(
+            history.clone(),
+            Arc::new(RwLock::new(history)),
+            bindings,
+            listener.modes.clone(),
+            Arc::clone(&listener.paused),
+            Arc::clone(&listener.events),
+        )
+        .await;
+        assert_eq!(listener.mode().await, Some("held".into()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handle_event_single_or_double_click() {
+        use crate::mode::ModeChange;
+
+        let listener = Listener::default();
+        let single = Binding::new(KeySet::from(BindKey::new(vec![Key::KeyMouseLeft])), noop)
+            .switch_mode(ModeChange::Push("single".into()));
+        let double = Binding::new(
+            Trigger::tap_count(Key::KeyMouseLeft, 2, Duration::from_millis(200)),
+            noop,
+        )
+        .switch_mode(ModeChange::Push("double".into()));
+        listener.register_binding(single).await.unwrap();
+        listener.register_binding(double).await.unwrap();
+
+        // Event times follow the paused clock.
+        let (start, base) = (tokio::time::Instant::now(), SystemTime::now());
+        let click = |clicks| {
+            let listener = listener.clone();
+            async move {
+                for _ in 0..clicks {
+                    for event_type in [
+                        EventType::ButtonPress(rdev::Button::Left),
+                        EventType::ButtonRelease(rdev::Button::Left),
+                    ] {
+                        let event = event_at(base + start.elapsed(), event_type);
+                        handle_event(event, listener.clone()).await;
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                    }
+                }
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+        };
+
+        click(2).await;
+        assert_eq!(listener.modes.stack().await, vec!["double"]);
+        click(1).await;
+        assert_eq!(listener.modes.stack().await, vec!["double", "single"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_handle_event_tap_fires_on_release() {
+        use crate::mode::ModeChange;
+
+        let listener = Listener::default();
+        let tap = Binding::new("F".parse::<KeySet>().unwrap(), noop)
+            .switch_mode(ModeChange::Push("tap".into()));
+        let hold = Binding::new(Trigger::hold(Key::KeyF, Duration::from_millis(800)), noop)
+            .switch_mode(ModeChange::Push("held".into()));
+        listener.register_binding(tap).await.unwrap();
+        listener.register_binding(hold).await.unwrap();
+
+        // Event times follow the paused clock.
+        let (start, base) = (tokio::time::Instant::now(), SystemTime::now());
+        let event = |event_type| event_at(base + start.elapsed(), event_type);
+        handle_event(
+            event(EventType::KeyPress(rdev::Key::KeyF)),
+            listener.clone(),
+        )
+        .await;
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(listener.mode().await, None);
+
+        handle_event(
+            event(EventType::KeyRelease(rdev::Key::KeyF)),
+            listener.clone(),
+        )
+        .await;
+        let released = tokio::time::Instant::now();
+        while listener.mode().await.is_none() && released.elapsed() < Duration::from_secs(1) {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        // The release rules the hold out, the tap does not sit out its 800ms.
+        assert!(released.elapsed() < Duration::from_millis(10));
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        assert_eq!(listener.modes.stack().await, vec!["tap"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hook_tap_count_matches_snapshot() {
+        let now = SystemTime::now();
+        let history = History::from(vec![
+            event_at(now, EventType::ButtonPress(rdev::Button::Left)),
+            event_at(now, EventType::ButtonRelease(rdev::Button::Left)),
+            event_at(now, EventType::ButtonPress(rdev::Button::Left)),
+        ]);
+        // The pointer moves before the hook gets to look at the history.
+        let history_arc = Arc::new(RwLock::new(history.clone()));
+        history_arc
+            .write()
+            .await
+            .push(event_at(now, EventType::MouseMove { x: 1.0, y: 1.0 }));
+
+        let (calls, hook) = recorder();
+        let within = Duration::from_millis(200);
+        hook_tap_count(Key::KeyMouseLeft, 2, within, history, history_arc, hook)
+            .await
+            .unwrap();
+        assert_eq!(calls.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_handle_event_notifies_pending() {
+        let listener = Listener::default();
+        let key_set: KeySet = "Ctrl+K Ctrl+C".parse().unwrap();
+        listener.register(key_set.clone(), noop).await.unwrap();
+        let mut notifications = listener.subscribe();
+
+        for key in [rdev::Key::ControlLeft, rdev::Key::KeyK] {
+            let event = event_at(SystemTime::now(), EventType::KeyPress(key));
+            handle_event(event, listener.clone()).await;
+        }
+        let Ok(Notification::Pending(pending)) = notifications.recv().await else {
+            panic!("expected a pending sequence");
+        };
+        assert_eq!(pending.candidates[0].key_set, key_set);
+        assert_eq!(listener.pending().await, Some(pending));
+
+        let event = event_at(SystemTime::now(), EventType::KeyPress(rdev::Key::KeyX));
+        handle_event(event, listener.clone()).await;
+        assert_eq!(notifications.recv().await, Ok(Notification::PendingCleared));
+        assert_eq!(listener.pending().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_handle_event_cancels_pending() {
+        let listener = Listener::default();
+        let key_set: KeySet = "Ctrl+K Ctrl+C".parse().unwrap();
+        listener.register(key_set.clone(), noop).await.unwrap();
+        let mut notifications = listener.subscribe();
+
+        for key in [rdev::Key::ControlLeft, rdev::Key::KeyK, rdev::Key::Escape] {
+            let event = event_at(SystemTime::now(), EventType::KeyPress(key));
+            handle_event(event, listener.clone()).await;
+        }
+        let Ok(Notification::Pending(pending)) = notifications.recv().await else {
+            panic!("expected a pending sequence");
+        };
+        assert_eq!(
+            notifications.recv().await,
+            Ok(Notification::SequenceCancelled(
+                pending,
+                CancelReason::CancelKey
+            ))
+        );
+        assert!(!listener.cancel().await);
+
+        // The presses before the cancel key stay, but cannot complete it.
+        let mut history = listener.history.read().await.clone();
+        assert_eq!(history.len(), 3);
+        history.push(event_at(
+            SystemTime::now(),
+            EventType::KeyPress(rdev::Key::ControlLeft),
+        ));
+        history.push(event_at(
+            SystemTime::now(),
+            EventType::KeyPress(rdev::Key::KeyC),
+        ));
+        assert!(!match_keyset(&key_set, &history));
+    }
+
+    #[tokio::test]
+    async fn test_handle_event_sequence_timeout() {
+        let listener = Listener::default().sequence_timeout(Duration::from_millis(50));
+        let key_set: KeySet = "Ctrl+K Ctrl+C".parse().unwrap();
+        listener.register(key_set, noop).await.unwrap();
+        let mut notifications = listener.subscribe();
+
+        for key in [rdev::Key::ControlLeft, rdev::Key::KeyK] {
+            let event = event_at(SystemTime::now(), EventType::KeyPress(key));
+            handle_event(event, listener.clone()).await;
+        }
+        assert!(matches!(
+            notifications.recv().await,
+            Ok(Notification::Pending(_))
+        ));
+        assert!(matches!(
+            notifications.recv().await,
+            Ok(Notification::SequenceCancelled(_, CancelReason::Timeout))
+        ));
+        assert_eq!(listener.pending().await, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_sequence_timeout_cancels_waiting_prefix() {
+        use crate::mode::ModeChange;
+
+        let listener = Listener::default().sequence_timeout(Duration::from_millis(200));
+        let prefix = Binding::new("Ctrl+K".parse::<KeySet>().unwrap(), noop)
+            .switch_mode(ModeChange::Push("prefix".into()));
+        listener.register_binding(prefix).await.unwrap();
+        listener
+            .register("Ctrl+K Ctrl+C".parse::<KeySet>().unwrap(), noop)
+            .await
+            .unwrap();
+        let mut notifications = listener.subscribe();
+
+        for key in [rdev::Key::ControlLeft, rdev::Key::KeyK] {
+            let event = event_at(SystemTime::now(), EventType::KeyPress(key));
+            handle_event(event, listener.clone()).await;
+        }
+        // Ctrl+K waits for the second step longer than the sequence may last.
+        tokio::time::sleep(CONTINUATION_TIMEOUT * 2).await;
+        assert!(matches!(
+            notifications.recv().await,
+            Ok(Notification::Pending(_))
+        ));
+        assert!(matches!(
+            notifications.recv().await,
+            Ok(Notification::SequenceCancelled(_, CancelReason::Timeout))
+        ));
+        assert_eq!(listener.modes.current().await, None);
+    }
+
+    #[test]
+    fn test_count_prefix() {
+        let key_set = KeySet::from(BindKey::new(vec![Key::KeyCtrl, Key::KeyJ]));
+        let count = |keys: &[rdev::Key]| count_prefix(&key_set, &presses(keys));
+        assert_eq!(
+            count(&[
+                rdev::Key::Num1,
+                rdev::Key::Num2,
+                rdev::Key::ControlLeft,
+                rdev::Key::KeyJ
+            ]),
+            Some(12)
+        );
+        assert_eq!(
+            count(&[
+                rdev::Key::Num3,
+                rdev::Key::KeyA,
+                rdev::Key::Num0,
+                rdev::Key::Num4,
+                rdev::Key::ControlLeft,
+                rdev::Key::KeyJ
+            ]),
+            Some(4)
+        );
+        assert_eq!(
+            count(&[rdev::Key::Num0, rdev::Key::ControlLeft, rdev::Key::KeyJ]),
+            None
+        );
+        assert_eq!(count(&[rdev::Key::ControlLeft, rdev::Key::KeyJ]), None);
+        assert_eq!(
+            count(&[
+                rdev::Key::Num9,
+                rdev::Key::Num9,
+                rdev::Key::Num9,
+                rdev::Key::ControlLeft,
+                rdev::Key::KeyJ
+            ]),
+            Some(DEFAULT_MAX_COUNT)
+        );
+    }
+
+    #[test]
+    fn test_count_prefix_typed_earlier() {
+        let j = BindKey::new(vec![Key::KeyJ]);
+        let k = BindKey::new(vec![Key::KeyK]);
+        let mut history = History::new(16);
+        history.push(event_at(base_time(), EventType::KeyPress(rdev::Key::Num5)));
+        let later = base_time() + Duration::from_secs(60);
+        history.push(event_at(later, EventType::KeyPress(rdev::Key::KeyJ)));
+        history.push(event_at(later, EventType::KeyPress(rdev::Key::KeyK)));
+        let key_set = KeySet::default().bind(j.clone()).bind(k.clone());
+        assert_eq!(count_prefix(&key_set, &history), None);
+
+        // Unless the sequence allows that long a gap.
+        let slow = KeySet::default()
+            .bind(j.clone().delay(Duration::from_secs(90)))
+            .bind(k);
+        assert_eq!(count_prefix(&slow, &history), Some(5));
+    }
+
+    #[tokio::test]
+    async fn test_hook_keyset_repeats_count() {
+        let key_set = KeySet::from(BindKey::new(vec![Key::KeyCtrl, Key::KeyJ]))
+            .count_prefix(CountPrefix::Repeat);
+        let history = presses(&[rdev::Key::Num3, rdev::Key::ControlLeft, rdev::Key::KeyJ]);
+        let history_arc = Arc::new(RwLock::new(history.clone()));
+        let (calls, hook) = recorder();
+        hook_keyset(key_set, history, history_arc, hook)
+            .await
+            .unwrap();
+        let calls = calls.lock().unwrap();
+        assert_eq!(calls.len(), 3);
+        assert!(calls.iter().all(|context| context.count == Some(3)));
+    }
 }
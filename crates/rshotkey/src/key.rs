@@ -1,10 +1,11 @@
 use anyhow::Result;
 use rdev::EventType;
-use std::time::Duration;
+use std::{fmt, str::FromStr, time::Duration};
 
-use crate::exception::Error;
+use crate::{event::Button, exception::Error};
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[repr(u16)]
 pub enum Key {
     KeyUnknown,
     KeyA,
@@ -43,10 +44,18 @@ pub enum Key {
     Key8,
     Key9,
     Key0,
+    KeyCtrl,
     KeyLeftCtrl,
     KeyRightCtrl,
     KeyShift,
+    KeyLeftShift,
+    KeyRightShift,
     KeyAlt,
+    KeyLeftAlt,
+    KeyRightAlt,
+    KeyMeta,
+    KeyLeftMeta,
+    KeyRightMeta,
     KeyEnter,
     KeyEscape,
     KeyBackspace,
@@ -90,138 +99,508 @@ pub enum Key {
     KeyDown,
     KeyUp,
     KeyNumLock,
+    KeyKp1,
+    KeyKp2,
+    KeyKp3,
+    KeyKp4,
+    KeyKp5,
+    KeyKp6,
+    KeyKp7,
+    KeyKp8,
+    KeyKp9,
+    KeyKp0,
+    KeyKpMinus,
+    KeyKpPlus,
+    KeyKpMultiply,
+    KeyKpDivide,
+    KeyMouseLeft,
+    KeyMouseRight,
+    KeyMouseMiddle,
+    KeyAnyDigit,
+    KeyAnyLetter,
+    KeyAnyFunctionKey,
+    KeyAnyModifier,
+    KeyAny,
+}
+
+const KEY_NAMES: &[(Key, &str)] = &[
+    (Key::KeyUnknown, "Unknown"),
+    (Key::KeyA, "A"),
+    (Key::KeyB, "B"),
+    (Key::KeyC, "C"),
+    (Key::KeyD, "D"),
+    (Key::KeyE, "E"),
+    (Key::KeyF, "F"),
+    (Key::KeyG, "G"),
+    (Key::KeyH, "H"),
+    (Key::KeyI, "I"),
+    (Key::KeyJ, "J"),
+    (Key::KeyK, "K"),
+    (Key::KeyL, "L"),
+    (Key::KeyM, "M"),
+    (Key::KeyN, "N"),
+    (Key::KeyO, "O"),
+    (Key::KeyP, "P"),
+    (Key::KeyQ, "Q"),
+    (Key::KeyR, "R"),
+    (Key::KeyS, "S"),
+    (Key::KeyT, "T"),
+    (Key::KeyU, "U"),
+    (Key::KeyV, "V"),
+    (Key::KeyW, "W"),
+    (Key::KeyX, "X"),
+    (Key::KeyY, "Y"),
+    (Key::KeyZ, "Z"),
+    (Key::Key1, "1"),
+    (Key::Key2, "2"),
+    (Key::Key3, "3"),
+    (Key::Key4, "4"),
+    (Key::Key5, "5"),
+    (Key::Key6, "6"),
+    (Key::Key7, "7"),
+    (Key::Key8, "8"),
+    (Key::Key9, "9"),
+    (Key::Key0, "0"),
+    (Key::KeyCtrl, "Ctrl"),
+    (Key::KeyLeftCtrl, "LeftCtrl"),
+    (Key::KeyRightCtrl, "RightCtrl"),
+    (Key::KeyShift, "Shift"),
+    (Key::KeyLeftShift, "LeftShift"),
+    (Key::KeyRightShift, "RightShift"),
+    (Key::KeyAlt, "Alt"),
+    (Key::KeyLeftAlt, "LeftAlt"),
+    (Key::KeyRightAlt, "RightAlt"),
+    (Key::KeyMeta, "Meta"),
+    (Key::KeyLeftMeta, "LeftMeta"),
+    (Key::KeyRightMeta, "RightMeta"),
+    (Key::KeyEnter, "Enter"),
+    (Key::KeyEscape, "Escape"),
+    (Key::KeyBackspace, "Backspace"),
+    (Key::KeyTab, "Tab"),
+    (Key::KeySpace, "Space"),
+    (Key::KeyMinus, "Minus"),
+    (Key::KeyEqual, "Equal"),
+    (Key::KeyLeftBracket, "LeftBracket"),
+    (Key::KeyRightBracket, "RightBracket"),
+    (Key::KeyBackslash, "Backslash"),
+    (Key::KeySemicolon, "Semicolon"),
+    (Key::KeyApostrophe, "Apostrophe"),
+    (Key::KeyGrave, "Grave"),
+    (Key::KeyComma, "Comma"),
+    (Key::KeyDot, "Dot"),
+    (Key::KeySlash, "Slash"),
+    (Key::KeyCapsLock, "CapsLock"),
+    (Key::KeyF1, "F1"),
+    (Key::KeyF2, "F2"),
+    (Key::KeyF3, "F3"),
+    (Key::KeyF4, "F4"),
+    (Key::KeyF5, "F5"),
+    (Key::KeyF6, "F6"),
+    (Key::KeyF7, "F7"),
+    (Key::KeyF8, "F8"),
+    (Key::KeyF9, "F9"),
+    (Key::KeyF10, "F10"),
+    (Key::KeyF11, "F11"),
+    (Key::KeyF12, "F12"),
+    (Key::KeyPrintScreen, "PrintScreen"),
+    (Key::KeyScrollLock, "ScrollLock"),
+    (Key::KeyPause, "Pause"),
+    (Key::KeyInsert, "Insert"),
+    (Key::KeyHome, "Home"),
+    (Key::KeyPageUp, "PageUp"),
+    (Key::KeyDelete, "Delete"),
+    (Key::KeyEnd, "End"),
+    (Key::KeyPageDown, "PageDown"),
+    (Key::KeyRight, "Right"),
+    (Key::KeyLeft, "Left"),
+    (Key::KeyDown, "Down"),
+    (Key::KeyUp, "Up"),
+    (Key::KeyNumLock, "NumLock"),
+    (Key::KeyKp1, "Kp1"),
+    (Key::KeyKp2, "Kp2"),
+    (Key::KeyKp3, "Kp3"),
+    (Key::KeyKp4, "Kp4"),
+    (Key::KeyKp5, "Kp5"),
+    (Key::KeyKp6, "Kp6"),
+    (Key::KeyKp7, "Kp7"),
+    (Key::KeyKp8, "Kp8"),
+    (Key::KeyKp9, "Kp9"),
+    (Key::KeyKp0, "Kp0"),
+    (Key::KeyKpMinus, "KpMinus"),
+    (Key::KeyKpPlus, "KpPlus"),
+    (Key::KeyKpMultiply, "KpMultiply"),
+    (Key::KeyKpDivide, "KpDivide"),
+    (Key::KeyMouseLeft, "MouseLeft"),
+    (Key::KeyMouseRight, "MouseRight"),
+    (Key::KeyMouseMiddle, "MouseMiddle"),
+    (Key::KeyAnyDigit, "AnyDigit"),
+    (Key::KeyAnyLetter, "AnyLetter"),
+    (Key::KeyAnyFunctionKey, "AnyFunctionKey"),
+    (Key::KeyAnyModifier, "AnyModifier"),
+    (Key::KeyAny, "AnyKey"),
+];
+
+impl Key {
+    pub fn is_modifier(&self) -> bool {
+        matches!(
+            self,
+            Key::KeyCtrl
+                | Key::KeyLeftCtrl
+                | Key::KeyRightCtrl
+                | Key::KeyShift
+                | Key::KeyLeftShift
+                | Key::KeyRightShift
+                | Key::KeyAlt
+                | Key::KeyLeftAlt
+                | Key::KeyRightAlt
+                | Key::KeyMeta
+                | Key::KeyLeftMeta
+                | Key::KeyRightMeta
+                | Key::KeyAnyModifier
+        )
+    }
+
+    /// Whether this is a digit key, either on the main row or the keypad.
+    pub fn is_digit(&self) -> bool {
+        self.digit().is_some()
+    }
+
+    /// The value of a digit key, `Key0` and `KeyKp0` being zero.
+    pub fn digit(&self) -> Option<u32> {
+        let code = *self as u16;
+        [Key::Key1, Key::KeyKp1]
+            .into_iter()
+            .map(|first| first as u16)
+            .find(|first| (*first..first + 10).contains(&code))
+            .map(|first| (code - first + 1) as u32 % 10)
+    }
+
+    pub fn is_letter(&self) -> bool {
+        (Key::KeyA as u16..=Key::KeyZ as u16).contains(&(*self as u16))
+    }
+
+    pub fn is_function(&self) -> bool {
+        (Key::KeyF1 as u16..=Key::KeyF12 as u16).contains(&(*self as u16))
+    }
+
+    pub fn is_mouse(&self) -> bool {
+        matches!(
+            self,
+            Key::KeyMouseLeft | Key::KeyMouseRight | Key::KeyMouseMiddle
+        )
+    }
+
+    /// Whether this is a key class such as [`Key::KeyAnyDigit`] that stands
+    /// for several physical keys.
+    pub fn is_class(&self) -> bool {
+        matches!(
+            self,
+            Key::KeyAnyDigit
+                | Key::KeyAnyLetter
+                | Key::KeyAnyFunctionKey
+                | Key::KeyAnyModifier
+                | Key::KeyAny
+        )
+    }
+
+    /// The side-agnostic form of a modifier, other keys are returned as is.
+    pub fn generic(&self) -> Key {
+        match self {
+            Key::KeyLeftCtrl | Key::KeyRightCtrl => Key::KeyCtrl,
+            Key::KeyLeftShift | Key::KeyRightShift => Key::KeyShift,
+            Key::KeyLeftAlt | Key::KeyRightAlt => Key::KeyAlt,
+            Key::KeyLeftMeta | Key::KeyRightMeta => Key::KeyMeta,
+            _ => *self,
+        }
+    }
+
+    /// Whether a bound key accepts the physical `key`, a generic modifier
+    /// accepts both of its sides and a class any of its members.
+    pub fn matches(&self, key: Key) -> bool {
+        if key.is_class() {
+            return *self == key;
+        }
+        match self {
+            Key::KeyAnyDigit => key.is_digit(),
+            Key::KeyAnyLetter => key.is_letter(),
+            Key::KeyAnyFunctionKey => key.is_function(),
+            Key::KeyAnyModifier => key.is_modifier(),
+            Key::KeyAny => !key.is_mouse(),
+            _ => *self == key || *self == key.generic(),
+        }
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = KEY_NAMES
+            .iter()
+            .find(|(key, _)| key == self)
+            .map_or("Unknown", |(_, name)| name);
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Key {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let name = s.trim();
+        let name = match name.to_ascii_lowercase().as_str() {
+            "esc" => "Escape",
+            "control" => "Ctrl",
+            "super" | "win" | "cmd" | "command" => "Meta",
+            "return" => "Enter",
+            "del" => "Delete",
+            "ins" => "Insert",
+            "pgup" => "PageUp",
+            "pgdn" => "PageDown",
+            _ => name,
+        };
+        KEY_NAMES
+            .iter()
+            .find(|(_, key_name)| key_name.eq_ignore_ascii_case(name))
+            .map(|(key, _)| *key)
+            .ok_or_else(|| Error::UnknownKey(s.trim().to_string()))
+    }
+}
+
+impl From<rdev::Key> for Key {
+    fn from(value: rdev::Key) -> Self {
+        match value {
+            rdev::Key::Alt => Key::KeyLeftAlt,
+            rdev::Key::AltGr => Key::KeyRightAlt,
+            rdev::Key::Backspace => Key::KeyBackspace,
+            rdev::Key::CapsLock => Key::KeyCapsLock,
+            rdev::Key::ControlLeft => Key::KeyLeftCtrl,
+            rdev::Key::ControlRight => Key::KeyRightCtrl,
+            rdev::Key::Delete => Key::KeyDelete,
+            rdev::Key::DownArrow => Key::KeyDown,
+            rdev::Key::End => Key::KeyEnd,
+            rdev::Key::Escape => Key::KeyEscape,
+            rdev::Key::F1 => Key::KeyF1,
+            rdev::Key::F2 => Key::KeyF2,
+            rdev::Key::F3 => Key::KeyF3,
+            rdev::Key::F4 => Key::KeyF4,
+            rdev::Key::F5 => Key::KeyF5,
+            rdev::Key::F6 => Key::KeyF6,
+            rdev::Key::F7 => Key::KeyF7,
+            rdev::Key::F8 => Key::KeyF8,
+            rdev::Key::F9 => Key::KeyF9,
+            rdev::Key::F10 => Key::KeyF10,
+            rdev::Key::F11 => Key::KeyF11,
+            rdev::Key::F12 => Key::KeyF12,
+            rdev::Key::Home => Key::KeyHome,
+            rdev::Key::LeftArrow => Key::KeyLeft,
+            rdev::Key::MetaLeft => Key::KeyLeftMeta,
+            rdev::Key::MetaRight => Key::KeyRightMeta,
+            rdev::Key::PageDown => Key::KeyPageDown,
+            rdev::Key::PageUp => Key::KeyPageUp,
+            rdev::Key::Return => Key::KeyEnter,
+            rdev::Key::RightArrow => Key::KeyRight,
+            rdev::Key::ShiftLeft => Key::KeyLeftShift,
+            rdev::Key::ShiftRight => Key::KeyRightShift,
+            rdev::Key::Space => Key::KeySpace,
+            rdev::Key::Tab => Key::KeyTab,
+            rdev::Key::UpArrow => Key::KeyUp,
+            rdev::Key::PrintScreen => Key::KeyPrintScreen,
+            rdev::Key::ScrollLock => Key::KeyScrollLock,
+            rdev::Key::Pause => Key::KeyPause,
+            rdev::Key::NumLock => Key::KeyNumLock,
+            rdev::Key::BackQuote => Key::KeyGrave,
+            rdev::Key::Num1 => Key::Key1,
+            rdev::Key::Num2 => Key::Key2,
+            rdev::Key::Num3 => Key::Key3,
+            rdev::Key::Num4 => Key::Key4,
+            rdev::Key::Num5 => Key::Key5,
+            rdev::Key::Num6 => Key::Key6,
+            rdev::Key::Num7 => Key::Key7,
+            rdev::Key::Num8 => Key::Key8,
+            rdev::Key::Num9 => Key::Key9,
+            rdev::Key::Num0 => Key::Key0,
+            rdev::Key::Minus => Key::KeyMinus,
+            rdev::Key::Equal => Key::KeyEqual,
+            rdev::Key::LeftBracket => Key::KeyLeftBracket,
+            rdev::Key::RightBracket => Key::KeyRightBracket,
+            rdev::Key::SemiColon => Key::KeySemicolon,
+            rdev::Key::Quote => Key::KeyApostrophe,
+            rdev::Key::BackSlash => Key::KeyBackslash,
+            rdev::Key::IntlBackslash => Key::KeyBackslash,
+            rdev::Key::KeyA => Key::KeyA,
+            rdev::Key::KeyB => Key::KeyB,
+            rdev::Key::KeyC => Key::KeyC,
+            rdev::Key::KeyD => Key::KeyD,
+            rdev::Key::KeyE => Key::KeyE,
+            rdev::Key::KeyF => Key::KeyF,
+            rdev::Key::KeyG => Key::KeyG,
+            rdev::Key::KeyH => Key::KeyH,
+            rdev::Key::KeyI => Key::KeyI,
+            rdev::Key::KeyJ => Key::KeyJ,
+            rdev::Key::KeyK => Key::KeyK,
+            rdev::Key::KeyL => Key::KeyL,
+            rdev::Key::KeyM => Key::KeyM,
+            rdev::Key::KeyN => Key::KeyN,
+            rdev::Key::KeyO => Key::KeyO,
+            rdev::Key::KeyP => Key::KeyP,
+            rdev::Key::KeyQ => Key::KeyQ,
+            rdev::Key::KeyR => Key::KeyR,
+            rdev::Key::KeyS => Key::KeyS,
+            rdev::Key::KeyT => Key::KeyT,
+            rdev::Key::KeyU => Key::KeyU,
+            rdev::Key::KeyV => Key::KeyV,
+            rdev::Key::KeyW => Key::KeyW,
+            rdev::Key::KeyX => Key::KeyX,
+            rdev::Key::KeyY => Key::KeyY,
+            rdev::Key::KeyZ => Key::KeyZ,
+            rdev::Key::Comma => Key::KeyComma,
+            rdev::Key::Dot => Key::KeyDot,
+            rdev::Key::Slash => Key::KeySlash,
+            rdev::Key::Insert => Key::KeyInsert,
+            rdev::Key::KpReturn => Key::KeyEnter,
+            rdev::Key::KpMinus => Key::KeyKpMinus,
+            rdev::Key::KpPlus => Key::KeyKpPlus,
+            rdev::Key::KpMultiply => Key::KeyKpMultiply,
+            rdev::Key::KpDivide => Key::KeyKpDivide,
+            rdev::Key::Kp0 => Key::KeyKp0,
+            rdev::Key::Kp1 => Key::KeyKp1,
+            rdev::Key::Kp2 => Key::KeyKp2,
+            rdev::Key::Kp3 => Key::KeyKp3,
+            rdev::Key::Kp4 => Key::KeyKp4,
+            rdev::Key::Kp5 => Key::KeyKp5,
+            rdev::Key::Kp6 => Key::KeyKp6,
+            rdev::Key::Kp7 => Key::KeyKp7,
+            rdev::Key::Kp8 => Key::KeyKp8,
+            rdev::Key::Kp9 => Key::KeyKp9,
+            rdev::Key::KpDelete => Key::KeyDelete,
+            rdev::Key::Function => Key::KeyUnknown,
+            rdev::Key::Unknown(_) => Key::KeyUnknown,
+        }
+    }
+}
+
+impl From<Button> for Key {
+    fn from(value: Button) -> Self {
+        match value {
+            Button::Left => Key::KeyMouseLeft,
+            Button::Right => Key::KeyMouseRight,
+            Button::Middle => Key::KeyMouseMiddle,
+            Button::Unknown(_) => Key::KeyUnknown,
+        }
+    }
 }
 
 impl From<EventType> for Key {
     fn from(value: EventType) -> Self {
         match value {
-            EventType::KeyPress(key) => match key {
-                rdev::Key::Alt => Key::KeyAlt,
-                rdev::Key::AltGr => todo!(),
-                rdev::Key::Backspace => Key::KeyBackspace,
-                rdev::Key::CapsLock => Key::KeyCapsLock,
-                rdev::Key::ControlLeft => Key::KeyLeftCtrl,
-                rdev::Key::ControlRight => Key::KeyRightCtrl,
-                rdev::Key::Delete => Key::KeyDelete,
-                rdev::Key::DownArrow => Key::KeyDown,
-                rdev::Key::End => Key::KeyEnd,
-                rdev::Key::Escape => Key::KeyEscape,
-                rdev::Key::F1 => Key::KeyF1,
-                rdev::Key::F2 => Key::KeyF2,
-                rdev::Key::F3 => Key::KeyF3,
-                rdev::Key::F4 => Key::KeyF4,
-                rdev::Key::F5 => Key::KeyF5,
-                rdev::Key::F6 => Key::KeyF6,
-                rdev::Key::F7 => Key::KeyF7,
-                rdev::Key::F8 => Key::KeyF8,
-                rdev::Key::F9 => Key::KeyF9,
-                rdev::Key::F10 => Key::KeyF10,
-                rdev::Key::F11 => Key::KeyF11,
-                rdev::Key::F12 => Key::KeyF12,
-                rdev::Key::Home => Key::KeyHome,
-                rdev::Key::LeftArrow => Key::KeyLeft,
-                rdev::Key::MetaLeft => todo!(),
-                rdev::Key::MetaRight => todo!(),
-                rdev::Key::PageDown => todo!(),
-                rdev::Key::PageUp => Key::KeyPageUp,
-                rdev::Key::Return => Key::KeyEnter,
-                rdev::Key::RightArrow => todo!(),
-                rdev::Key::ShiftLeft => todo!(),
-                rdev::Key::ShiftRight => todo!(),
-                rdev::Key::Space => Key::KeySpace,
-                rdev::Key::Tab => todo!(),
-                rdev::Key::UpArrow => todo!(),
-                rdev::Key::PrintScreen => todo!(),
-                rdev::Key::ScrollLock => todo!(),
-                rdev::Key::Pause => todo!(),
-                rdev::Key::NumLock => todo!(),
-                rdev::Key::BackQuote => todo!(),
-                rdev::Key::Num1 => Key::Key1,
-                rdev::Key::Num2 => Key::Key2,
-                rdev::Key::Num3 => Key::Key3,
-                rdev::Key::Num4 => Key::Key4,
-                rdev::Key::Num5 => Key::Key5,
-                rdev::Key::Num6 => Key::Key6,
-                rdev::Key::Num7 => Key::Key7,
-                rdev::Key::Num8 => Key::Key8,
-                rdev::Key::Num9 => Key::Key9,
-                rdev::Key::Num0 => Key::Key0,
-                rdev::Key::Minus => Key::KeyMinus,
-                rdev::Key::Equal => Key::KeyEqual,
-                rdev::Key::LeftBracket => Key::KeyLeftBracket,
-                rdev::Key::RightBracket => Key::KeyRightBracket,
-                rdev::Key::SemiColon => Key::KeySemicolon,
-                rdev::Key::Quote => todo!(),
-                rdev::Key::BackSlash => Key::KeyBackslash,
-                rdev::Key::IntlBackslash => Key::KeyBackslash,
-                rdev::Key::KeyA => Key::KeyA,
-                rdev::Key::KeyB => Key::KeyB,
-                rdev::Key::KeyC => Key::KeyC,
-                rdev::Key::KeyD => Key::KeyD,
-                rdev::Key::KeyE => Key::KeyE,
-                rdev::Key::KeyF => Key::KeyF,
-                rdev::Key::KeyG => Key::KeyG,
-                rdev::Key::KeyH => Key::KeyH,
-                rdev::Key::KeyI => Key::KeyI,
-                rdev::Key::KeyJ => Key::KeyJ,
-                rdev::Key::KeyK => Key::KeyK,
-                rdev::Key::KeyL => Key::KeyL,
-                rdev::Key::KeyM => Key::KeyM,
-                rdev::Key::KeyN => Key::KeyN,
-                rdev::Key::KeyO => Key::KeyO,
-                rdev::Key::KeyP => Key::KeyP,
-                rdev::Key::KeyQ => Key::KeyQ,
-                rdev::Key::KeyR => Key::KeyR,
-                rdev::Key::KeyS => Key::KeyS,
-                rdev::Key::KeyT => Key::KeyT,
-                rdev::Key::KeyU => Key::KeyU,
-                rdev::Key::KeyV => Key::KeyV,
-                rdev::Key::KeyW => Key::KeyW,
-                rdev::Key::KeyX => Key::KeyX,
-                rdev::Key::KeyY => Key::KeyY,
-                rdev::Key::KeyZ => Key::KeyZ,
-                rdev::Key::Comma => Key::KeyComma,
-                rdev::Key::Dot => Key::KeyDot,
-                rdev::Key::Slash => Key::KeySlash,
-                rdev::Key::Insert => Key::KeyInsert,
-                rdev::Key::KpReturn => todo!(),
-                rdev::Key::KpMinus => todo!(),
-                rdev::Key::KpPlus => todo!(),
-                rdev::Key::KpMultiply => todo!(),
-                rdev::Key::KpDivide => todo!(),
-                rdev::Key::Kp0 => todo!(),
-                rdev::Key::Kp1 => todo!(),
-                rdev::Key::Kp2 => todo!(),
-                rdev::Key::Kp3 => todo!(),
-                rdev::Key::Kp4 => todo!(),
-                rdev::Key::Kp5 => todo!(),
-                rdev::Key::Kp6 => todo!(),
-                rdev::Key::Kp7 => todo!(),
-                rdev::Key::Kp8 => todo!(),
-                rdev::Key::Kp9 => todo!(),
-                rdev::Key::KpDelete => todo!(),
-                rdev::Key::Function => todo!(),
-                rdev::Key::Unknown(code) => match code {
-                    _ => Key::KeyUnknown,
-                },
-            },
+            EventType::KeyPress(key) => key.into(),
+            EventType::ButtonPress(button) => Button::from(button).into(),
             _ => Key::KeyUnknown,
         }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// A concrete key pressed for a key class or a generic modifier.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capture {
+    pub class: Key,
+    pub key: Key,
+}
+
+/// How the keys of a [`BindKey`] have to be pressed relative to each other.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ChordOrder {
+    /// Keys may be pressed in any order.
+    #[default]
+    Unordered,
+    /// Keys must be pressed exactly in the declared order.
+    Ordered,
+    /// All modifiers must be pressed before the remaining keys, which may
+    /// then come in any order.
+    ModifiersFirst,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "crate::serde_impl::RawBindKey"))]
 pub struct BindKey {
-    pub keys: Vec<Key>,
+    pub(crate) keys: Vec<Key>,
+    /// Maximum gap between the last press of this step and the first press
+    /// of the next one, zero disables the check. The last step has no next
+    /// one, see [`KeySet::settle`] instead.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::duration_ms"))]
     pub delay_time: Duration,
+    /// Maximum spread between the first and the last press of the chord,
+    /// zero disables the check.
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::duration_ms"))]
+    pub combo_time: Duration,
+    pub(crate) order: ChordOrder,
+    /// Keys that must not be held when the chord completes.
+    pub without: Vec<Key>,
 }
 
 impl BindKey {
+    /// Creates an unordered chord from `keys`, stored in canonical order so
+    /// that equivalent chords compare equal.
     pub fn new(keys: Vec<Key>) -> Self {
         Self {
-            keys,
+            keys: Self::normalize(keys, ChordOrder::Unordered),
+            delay_time: Duration::from_secs(0),
+            combo_time: Duration::from_secs(0),
+            order: ChordOrder::Unordered,
+            without: vec![],
+        }
+    }
+
+    /// Creates a chord whose keys must be pressed in the given order.
+    pub fn ordered(keys: Vec<Key>) -> Self {
+        Self {
+            keys: Self::normalize(keys, ChordOrder::Ordered),
             delay_time: Duration::from_secs(0),
+            combo_time: Duration::from_secs(0),
+            order: ChordOrder::Ordered,
+            without: vec![],
+        }
+    }
+
+    /// Removes duplicated keys. Unless `order` is [`ChordOrder::Ordered`],
+    /// keys are also sorted with modifiers first.
+    pub fn normalize(mut keys: Vec<Key>, order: ChordOrder) -> Vec<Key> {
+        if order == ChordOrder::Ordered {
+            let mut seen = vec![];
+            keys.retain(|key| {
+                let duplicated = seen.contains(key);
+                seen.push(*key);
+                !duplicated
+            });
+        } else {
+            keys.sort_by_key(|key| (!key.is_modifier(), *key));
+            keys.dedup();
         }
+        keys
+    }
+
+    /// Only matches while none of `keys` is held, e.g. `S` without `Shift`.
+    pub fn without(&mut self, keys: Vec<Key>) -> Self {
+        self.without = Self::normalize(keys, ChordOrder::Unordered);
+        self.to_owned()
+    }
+
+    /// Switches the matching mode. Switching to [`ChordOrder::Ordered`] keeps
+    /// the current key order, use [`BindKey::ordered`] to declare a sequence.
+    pub fn order(&mut self, order: ChordOrder) -> Self {
+        self.order = order;
+        self.keys = Self::normalize(std::mem::take(&mut self.keys), order);
+        self.to_owned()
+    }
+
+    pub fn chord_order(&self) -> ChordOrder {
+        self.order
+    }
+
+    pub fn keys(&self) -> &[Key] {
+        &self.keys
     }
 
     pub fn delay(&mut self, time: Duration) -> Self {
@@ -229,9 +608,22 @@ impl BindKey {
         self.to_owned()
     }
 
+    /// Turns the chord into a combo: all keys have to be pressed within
+    /// `window` of each other, like QMK combos. A key set starting with a
+    /// combo replaces its keys, bindings matching some of them wait out the
+    /// window before firing.
+    pub fn combo(&mut self, window: Duration) -> Self {
+        self.combo_time = window;
+        self.to_owned()
+    }
+
     pub fn len(&self) -> usize {
         self.keys.len()
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
 }
 
 impl From<Vec<EventType>> for BindKey {
@@ -240,31 +632,25 @@ impl From<Vec<EventType>> for BindKey {
         for event in value {
             keys.push(event.into());
         }
-        Self {
-            keys,
-            delay_time: Duration::from_secs(0),
-        }
+        Self::new(keys)
     }
 }
 
 impl From<EventType> for BindKey {
     fn from(value: EventType) -> Self {
-        Self {
-            keys: vec![value.into()],
-            delay_time: Duration::from_secs(0),
-        }
+        Self::new(vec![value.into()])
     }
 }
 
-impl Into<Vec<BindKey>> for BindKey {
-    fn into(self) -> Vec<BindKey> {
-        vec![self]
+impl From<BindKey> for Vec<BindKey> {
+    fn from(value: BindKey) -> Self {
+        vec![value]
     }
 }
 
-impl Into<Vec<BindKey>> for &BindKey {
-    fn into(self) -> Vec<BindKey> {
-        vec![self.clone()]
+impl From<&BindKey> for Vec<BindKey> {
+    fn from(value: &BindKey) -> Self {
+        vec![value.clone()]
     }
 }
 
@@ -277,14 +663,125 @@ impl IntoIterator for BindKey {
     }
 }
 
-#[derive(Debug, Clone, Default, PartialEq)]
+impl fmt::Display for BindKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, key) in self.keys.iter().enumerate() {
+            if idx > 0 {
+                f.write_str("+")?;
+            }
+            write!(f, "{}", key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses a chord such as `LeftCtrl+Shift+S`, the delay is left at zero.
+impl FromStr for BindKey {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(Error::EmptyHotkey);
+        }
+        let keys = s
+            .split('+')
+            .map(Key::from_str)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Self::new(keys))
+    }
+}
+
+/// Which modifiers may be held when a [`KeySet`] fires, checked against the
+/// keys held down at that moment.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ModifierPolicy {
+    /// Extra modifiers may be held, `Ctrl+S` also fires for `Ctrl+Shift+S`.
+    #[default]
+    Subset,
+    /// Only the modifiers of the final chord may be held.
+    Exact,
+}
+
+/// What a [`KeySet`] does with digits typed right before it, like the
+/// count in Vim's `3j`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CountPrefix {
+    /// Digits are not looked at.
+    #[default]
+    Off,
+    /// The count is passed to the hook, which is invoked once.
+    Pass,
+    /// The hook is invoked count times, once without a count.
+    Repeat,
+}
+
+/// Largest count a [`KeySet`] passes on unless set otherwise.
+pub const DEFAULT_MAX_COUNT: u32 = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct KeySet {
     pub bind_keys: Vec<BindKey>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub modifier_policy: ModifierPolicy,
+    /// Keys that must not be pressed right before the sequence.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub not_after: Vec<Key>,
+    /// Maximum time from the first to the last press of the whole sequence,
+    /// zero disables the check.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::serde_impl::duration_ms")
+    )]
+    pub deadline: Duration,
+    /// Time to wait after a match before firing, the match is dropped if
+    /// another press arrives meanwhile. Zero fires right away.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, with = "crate::serde_impl::duration_ms")
+    )]
+    pub settle: Duration,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub count_prefix: CountPrefix,
+    /// Larger counts are clamped to it, bounding how often
+    /// [`CountPrefix::Repeat`] invokes the hook.
+    #[cfg_attr(
+        feature = "serde",
+        serde(default = "crate::serde_impl::default_max_count")
+    )]
+    pub max_count: u32,
+}
+
+impl Default for KeySet {
+    fn default() -> Self {
+        Self::new(vec![])
+    }
 }
 
 impl KeySet {
     pub fn new(key: Vec<BindKey>) -> Self {
-        Self { bind_keys: key }
+        Self {
+            bind_keys: key,
+            modifier_policy: ModifierPolicy::default(),
+            not_after: vec![],
+            deadline: Duration::ZERO,
+            settle: Duration::ZERO,
+            count_prefix: CountPrefix::Off,
+            max_count: DEFAULT_MAX_COUNT,
+        }
+    }
+
+    pub fn modifiers(&mut self, policy: ModifierPolicy) -> Self {
+        self.modifier_policy = policy;
+        self.to_owned()
+    }
+
+    /// Only matches when the press preceding the sequence is none of `keys`.
+    pub fn not_after(&mut self, keys: Vec<Key>) -> Self {
+        self.not_after = BindKey::normalize(keys, ChordOrder::Unordered);
+        self.to_owned()
     }
 
     pub fn bind(&mut self, bind_key: BindKey) -> Self {
@@ -292,6 +789,46 @@ impl KeySet {
         self.to_owned()
     }
 
+    pub fn deadline(&mut self, deadline: Duration) -> Self {
+        self.deadline = deadline;
+        self.to_owned()
+    }
+
+    pub fn settle(&mut self, settle: Duration) -> Self {
+        self.settle = settle;
+        self.to_owned()
+    }
+
+    pub fn count_prefix(&mut self, count_prefix: CountPrefix) -> Self {
+        self.count_prefix = count_prefix;
+        self.to_owned()
+    }
+
+    pub fn max_count(&mut self, max_count: u32) -> Self {
+        self.max_count = max_count;
+        self.to_owned()
+    }
+
+    /// Checks that the timings make sense together: the last step sets no
+    /// gap, and no gap is longer than the deadline it could never use.
+    pub fn validate(&self) -> Result<()> {
+        if self.is_empty() {
+            return Err(Error::EmptyHotkey.into());
+        }
+        if self.last().is_some_and(|last| !last.delay_time.is_zero()) {
+            return Err(Error::TrailingDelay.into());
+        }
+        if !self.deadline.is_zero()
+            && self
+                .bind_keys
+                .iter()
+                .any(|bind_key| bind_key.delay_time > self.deadline)
+        {
+            return Err(Error::DeadlineTooShort.into());
+        }
+        Ok(())
+    }
+
     pub fn len(&self) -> usize {
         let mut count: usize = 0;
         for key in &self.bind_keys {
@@ -318,15 +855,13 @@ impl KeySet {
 
 impl From<Vec<BindKey>> for KeySet {
     fn from(value: Vec<BindKey>) -> Self {
-        Self { bind_keys: value }
+        Self::new(value)
     }
 }
 
 impl From<BindKey> for KeySet {
     fn from(value: BindKey) -> Self {
-        Self {
-            bind_keys: vec![value],
-        }
+        Self::new(vec![value])
     }
 }
 
@@ -354,3 +889,188 @@ impl IntoIterator for KeySet {
         self.bind_keys.into_iter()
     }
 }
+
+impl fmt::Display for KeySet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, bind_key) in self.bind_keys.iter().enumerate() {
+            if idx > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}", bind_key)?;
+        }
+        Ok(())
+    }
+}
+
+/// Parses whitespace separated chords such as `LeftCtrl+K LeftCtrl+C`.
+impl FromStr for KeySet {
+    type Err = Error;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Err(Error::EmptyHotkey);
+        }
+        let bind_keys = s
+            .split_whitespace()
+            .map(BindKey::from_str)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        Ok(Self::new(bind_keys))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_display_round_trip() {
+        for (key, name) in KEY_NAMES {
+            assert_eq!(key.to_string(), *name);
+            assert_eq!(&name.parse::<Key>().unwrap(), key);
+        }
+    }
+
+    #[test]
+    fn test_key_from_str() {
+        assert_eq!("a".parse::<Key>().unwrap(), Key::KeyA);
+        assert_eq!(" Esc ".parse::<Key>().unwrap(), Key::KeyEscape);
+        assert_eq!(
+            "Hyper".parse::<Key>(),
+            Err(Error::UnknownKey("Hyper".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_key_is_compact() {
+        assert_eq!(std::mem::size_of::<Key>(), 2);
+        assert!(Key::KeyA < Key::KeyB);
+    }
+
+    #[test]
+    fn test_key_set_as_map_key() {
+        let mut bindings = std::collections::HashMap::new();
+        let key_set: KeySet = "LeftCtrl+S".parse().unwrap();
+        bindings.insert(key_set.clone(), "save");
+        assert_eq!(bindings.get(&key_set), Some(&"save"));
+
+        let keys: std::collections::BTreeSet<Key> = [Key::KeyS, Key::KeyLeftCtrl, Key::KeyS].into();
+        assert_eq!(keys.len(), 2);
+    }
+
+    #[test]
+    fn test_bind_key_normalized() {
+        let ctrl_d = BindKey::new(vec![Key::KeyD, Key::KeyLeftCtrl]);
+        assert_eq!(
+            ctrl_d,
+            BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyD, Key::KeyD])
+        );
+        assert_eq!(ctrl_d.keys(), &[Key::KeyLeftCtrl, Key::KeyD]);
+        assert_eq!(ctrl_d.to_string(), "LeftCtrl+D");
+
+        let mut bindings = std::collections::HashSet::new();
+        bindings.insert(KeySet::from(ctrl_d));
+        assert!(bindings.contains(&"D+LeftCtrl".parse::<KeySet>().unwrap()));
+    }
+
+    #[test]
+    fn test_bind_key_ordered() {
+        let ordered = BindKey::ordered(vec![Key::KeyS, Key::KeyLeftCtrl, Key::KeyS]);
+        assert_eq!(ordered.keys(), &[Key::KeyS, Key::KeyLeftCtrl]);
+        assert_ne!(ordered, BindKey::new(vec![Key::KeyS, Key::KeyLeftCtrl]));
+        assert_eq!(
+            BindKey::new(vec![Key::KeyS, Key::KeyLeftCtrl]).order(ChordOrder::Unordered),
+            BindKey::ordered(vec![Key::KeyS, Key::KeyLeftCtrl]).order(ChordOrder::Unordered)
+        );
+    }
+
+    #[test]
+    fn test_generic_modifiers() {
+        assert!(Key::KeyCtrl.matches(Key::KeyLeftCtrl));
+        assert!(Key::KeyCtrl.matches(Key::KeyRightCtrl));
+        assert!(Key::KeyMeta.matches(Key::KeyRightMeta));
+        assert!(!Key::KeyLeftCtrl.matches(Key::KeyRightCtrl));
+        assert!(!Key::KeyShift.matches(Key::KeyLeftCtrl));
+        assert_eq!(Key::from(rdev::Key::ShiftRight), Key::KeyRightShift);
+        assert_eq!(
+            "control+super".parse::<BindKey>().unwrap().to_string(),
+            "Ctrl+Meta"
+        );
+    }
+
+    #[test]
+    fn test_key_digit() {
+        assert_eq!(Key::Key1.digit(), Some(1));
+        assert_eq!(Key::Key9.digit(), Some(9));
+        assert_eq!(Key::Key0.digit(), Some(0));
+        assert_eq!(Key::KeyA.digit(), None);
+        assert_eq!(Key::KeyKp7.digit(), Some(7));
+        assert_eq!(Key::KeyKp0.digit(), Some(0));
+        assert_eq!(Key::KeyKpMinus.digit(), None);
+        assert_eq!(Key::from(rdev::Key::Kp3), Key::KeyKp3);
+        assert_eq!(Key::from(rdev::Key::KpPlus), Key::KeyKpPlus);
+    }
+
+    #[test]
+    fn test_key_classes() {
+        assert!(Key::KeyAnyDigit.matches(Key::Key0));
+        assert!(Key::KeyAnyDigit.matches(Key::Key1));
+        assert!(Key::KeyAnyDigit.matches(Key::KeyKp5));
+        assert!(!Key::KeyAnyDigit.matches(Key::KeyA));
+        assert!(Key::KeyAnyLetter.matches(Key::KeyZ));
+        assert!(Key::KeyAnyFunctionKey.matches(Key::KeyF12));
+        assert!(Key::KeyAnyModifier.matches(Key::KeyRightAlt));
+        assert!(Key::KeyAny.matches(Key::KeyEnter));
+        assert!(!Key::KeyAny.matches(Key::KeyMouseLeft));
+        assert_eq!(
+            "Alt+AnyLetter".parse::<BindKey>().unwrap().keys(),
+            &[Key::KeyAlt, Key::KeyAnyLetter]
+        );
+    }
+
+    #[test]
+    fn test_key_set_validate() {
+        let ctrl_k = BindKey::new(vec![Key::KeyCtrl, Key::KeyK]);
+        let ctrl_c = BindKey::new(vec![Key::KeyCtrl, Key::KeyC]);
+        let key_set = KeySet::default()
+            .bind(ctrl_k.clone().delay(Duration::from_millis(500)))
+            .bind(ctrl_c.clone());
+        assert!(key_set.validate().is_ok());
+        assert!(key_set
+            .clone()
+            .deadline(Duration::from_secs(1))
+            .validate()
+            .is_ok());
+
+        let errors = [
+            (KeySet::default(), Error::EmptyHotkey),
+            (
+                KeySet::from(ctrl_c.clone().delay(Duration::from_millis(500))),
+                Error::TrailingDelay,
+            ),
+            (
+                key_set.clone().deadline(Duration::from_millis(200)),
+                Error::DeadlineTooShort,
+            ),
+        ];
+        for (key_set, error) in errors {
+            assert_eq!(
+                key_set.validate().unwrap_err().downcast::<Error>().unwrap(),
+                error
+            );
+        }
+    }
+
+    #[test]
+    fn test_key_set_from_str() {
+        let key_set: KeySet = "LeftCtrl+K LeftCtrl+C".parse().unwrap();
+        assert_eq!(
+            key_set,
+            KeySet::default()
+                .bind(BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyK]))
+                .bind(BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyC]))
+        );
+        assert_eq!(key_set.to_string(), "LeftCtrl+K LeftCtrl+C");
+        assert_eq!("  ".parse::<KeySet>(), Err(Error::EmptyHotkey));
+        assert!("LeftCtrl+".parse::<BindKey>().is_err());
+    }
+}
@@ -6,3 +6,10 @@ pub mod history;
 pub mod hooks;
 pub mod key;
 pub mod listener;
+pub mod mode;
+pub mod notification;
+pub mod profile;
+pub mod resolver;
+#[cfg(feature = "serde")]
+mod serde_impl;
+pub mod trigger;
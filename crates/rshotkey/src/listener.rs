@@ -1,85 +1,435 @@
-use std::{process, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    process,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, PoisonError,
+    },
+    time::Duration,
+};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use futures::future::BoxFuture;
-use rdev::{listen, Event};
-use tokio::{sync::RwLock, task::JoinHandle};
+use rdev::listen;
+use tokio::{
+    sync::{broadcast, Notify, RwLock},
+    task::JoinHandle,
+};
 
-use crate::{history::History, hooks::handle_event, key::KeySet};
+use crate::{
+    event::Event,
+    exception::Error,
+    history::History,
+    hooks::{cancel_pending, handle_event},
+    key::{BindKey, Capture, Key, KeySet},
+    mode::{ModeChange, Modes},
+    notification::{CancelReason, Notification, Notifier},
+    profile::{Profile, Settings},
+    resolver::Pending,
+    trigger::Trigger,
+};
 
 pub type HookResult = BoxFuture<'static, Result<()>>;
-pub type Hook = fn() -> HookResult;
+pub type Hook = fn(HookContext) -> HookResult;
+
+/// Details about the match a hook is invoked for.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookContext {
+    pub trigger: Trigger,
+    /// Concrete keys pressed for key classes and generic modifiers, in press
+    /// order.
+    pub captures: Vec<Capture>,
+    /// Digits typed before the key set, see [`crate::key::CountPrefix`].
+    pub count: Option<u32>,
+}
+
+/// A hook registered for a trigger, optionally scoped to a mode.
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub trigger: Trigger,
+    pub hook: Hook,
+    /// Mode the binding applies in, `None` for every mode.
+    pub mode: Option<String>,
+    /// Change to the mode stack applied right before the hook runs.
+    pub mode_change: Option<ModeChange>,
+    /// Named group the binding can be enabled and disabled with.
+    pub group: Option<String>,
+}
+
+impl Binding {
+    pub fn new(trigger: impl Into<Trigger>, hook: Hook) -> Self {
+        Self {
+            trigger: trigger.into(),
+            hook,
+            mode: None,
+            mode_change: None,
+            group: None,
+        }
+    }
+
+    pub fn in_mode(&mut self, mode: &str) -> Self {
+        self.mode = Some(mode.to_owned());
+        self.to_owned()
+    }
+
+    pub fn in_group(&mut self, group: &str) -> Self {
+        self.group = Some(group.to_owned());
+        self.to_owned()
+    }
+
+    /// Makes the binding enter or leave a mode when it fires.
+    pub fn switch_mode(&mut self, mode_change: ModeChange) -> Self {
+        self.mode_change = Some(mode_change);
+        self.to_owned()
+    }
+
+    /// Whether the binding applies while `mode` is current.
+    pub fn is_active(&self, mode: Option<&str>) -> bool {
+        self.mode.is_none() || self.mode.as_deref() == mode
+    }
+}
+
+impl From<(Trigger, Hook)> for Binding {
+    fn from((trigger, hook): (Trigger, Hook)) -> Self {
+        Self::new(trigger, hook)
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct Listener {
-    max_history: usize,
-    history: Arc<RwLock<History>>,
-    hooks: Arc<RwLock<Vec<(KeySet, Hook)>>>,
+    pub(crate) max_history: usize,
+    pub(crate) history: Arc<RwLock<History>>,
+    /// The loaded profile. A std lock, never held across an await, so the
+    /// builder methods can change its settings.
+    pub(crate) profile: Arc<std::sync::RwLock<Profile>>,
+    pub(crate) profiles: Arc<RwLock<HashMap<String, Profile>>>,
+    pub(crate) modes: Modes,
+    pub(crate) disabled_groups: Arc<RwLock<HashSet<String>>>,
+    pub(crate) pending: Arc<RwLock<Option<Pending>>>,
+    pub(crate) notifier: Notifier,
+    pub(crate) paused: Arc<AtomicBool>,
+    /// A std lock like `profile`, kept apart so it holds across profiles.
+    pub(crate) kill_switch: Arc<std::sync::RwLock<Option<KeySet>>>,
+    /// Woken on every event, for hooks waiting on what comes next.
+    pub(crate) events: Arc<Notify>,
 }
 
 impl Default for Listener {
     fn default() -> Self {
-        Self {
-            max_history: 512,
-            history: Default::default(),
-            hooks: Default::default(),
-        }
+        Self::new(512, History::default(), vec![])
     }
 }
 
 impl Listener {
-    pub fn new(max_history: usize, history: History, hooks: Vec<(KeySet, Hook)>) -> Self {
+    pub fn new(max_history: usize, history: History, hooks: Vec<(Trigger, Hook)>) -> Self {
+        let notifier = Notifier::new(64);
         Self {
             history: Arc::new(RwLock::new(history)),
-            hooks: Arc::new(RwLock::new(hooks)),
+            profile: Arc::new(std::sync::RwLock::new(Profile {
+                name: "default".to_owned(),
+                bindings: hooks.into_iter().map(Binding::from).collect(),
+                settings: Settings::default(),
+            })),
+            profiles: Default::default(),
+            modes: Modes::new(notifier.clone()),
+            disabled_groups: Default::default(),
             max_history,
+            pending: Default::default(),
+            notifier,
+            paused: Default::default(),
+            kill_switch: Default::default(),
+            events: Default::default(),
         }
     }
 
-    pub async fn register(&self, key_set: KeySet, callback: Hook) -> Result<()> {
-        if key_set.is_empty() {
-            Err(anyhow!("Key set should not be empty!"))
+    fn loaded(&self) -> std::sync::RwLockReadGuard<'_, Profile> {
+        self.profile.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn loaded_mut(&self) -> std::sync::RwLockWriteGuard<'_, Profile> {
+        self.profile.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Copy of the loaded profile, so one event sees its bindings and
+    /// settings as they were when it arrived.
+    pub(crate) fn loaded_profile(&self) -> Profile {
+        self.loaded().clone()
+    }
+
+    /// Settings of the loaded profile.
+    pub fn settings(&self) -> Settings {
+        self.loaded().settings.clone()
+    }
+
+    /// Key that cancels a sequence in progress, `Escape` by default. The
+    /// press is swallowed then, it only reaches hooks when nothing is
+    /// pending.
+    pub fn cancel_key(&mut self, key: Option<Key>) -> Self {
+        self.loaded_mut().settings.cancel_key = key;
+        self.to_owned()
+    }
+
+    /// Cancels a sequence in progress once this long has passed since its
+    /// first press, zero disables it.
+    pub fn sequence_timeout(&mut self, timeout: Duration) -> Self {
+        self.loaded_mut().settings.sequence_timeout = timeout;
+        self.to_owned()
+    }
+
+    pub async fn register(&self, trigger: impl Into<Trigger>, callback: Hook) -> Result<()> {
+        self.register_binding(Binding::new(trigger, callback)).await
+    }
+
+    /// Registers a binding that only applies while `mode` is current.
+    pub async fn register_in_mode(
+        &self,
+        mode: &str,
+        trigger: impl Into<Trigger>,
+        callback: Hook,
+    ) -> Result<()> {
+        self.register_binding(Binding::new(trigger, callback).in_mode(mode))
+            .await
+    }
+
+    /// Registers a binding into `group`, see [`Listener::disable_group`].
+    pub async fn register_in_group(
+        &self,
+        group: &str,
+        trigger: impl Into<Trigger>,
+        callback: Hook,
+    ) -> Result<()> {
+        self.register_binding(Binding::new(trigger, callback).in_group(group))
+            .await
+    }
+
+    pub async fn register_binding(&self, binding: Binding) -> Result<()> {
+        binding.trigger.validate()?;
+        self.loaded_mut().bindings.push(binding);
+        Ok(())
+    }
+
+    /// Removes every binding of `trigger`, whatever its mode.
+    pub async fn unregister(&self, trigger: impl Into<Trigger>) {
+        let trigger = trigger.into();
+        self.loaded_mut()
+            .bindings
+            .retain(|binding| binding.trigger != trigger);
+    }
+
+    /// Bindings applying in the current mode, leaving out disabled groups.
+    pub async fn active_bindings(&self) -> Vec<Binding> {
+        self.active_bindings_of(&self.loaded_profile()).await
+    }
+
+    /// Bindings of `profile` applying in the current mode, leaving out
+    /// disabled groups.
+    pub(crate) async fn active_bindings_of(&self, profile: &Profile) -> Vec<Binding> {
+        let mode = self.modes.current().await;
+        let disabled_groups = self.disabled_groups.read().await;
+        profile
+            .bindings
+            .iter()
+            .filter(|binding| binding.is_active(mode.as_deref()))
+            .filter(|binding| {
+                binding
+                    .group
+                    .as_ref()
+                    .is_none_or(|group| !disabled_groups.contains(group))
+            })
+            .cloned()
+            .collect()
+    }
+
+    /// Skips the bindings of `group` until it is enabled again, they stay
+    /// registered.
+    pub async fn disable_group(&self, group: &str) {
+        self.disabled_groups.write().await.insert(group.to_owned());
+    }
+
+    pub async fn enable_group(&self, group: &str) {
+        self.disabled_groups.write().await.remove(group);
+    }
+
+    pub async fn is_group_enabled(&self, group: &str) -> bool {
+        !self.disabled_groups.read().await.contains(group)
+    }
+
+    pub async fn mode(&self) -> Option<String> {
+        self.modes.current().await
+    }
+
+    pub async fn push_mode(&self, mode: &str) {
+        self.modes.apply(&ModeChange::Push(mode.to_owned())).await
+    }
+
+    pub async fn pop_mode(&self) {
+        self.modes.apply(&ModeChange::Pop).await
+    }
+
+    pub async fn toggle_mode(&self, mode: &str) {
+        self.modes.apply(&ModeChange::Toggle(mode.to_owned())).await
+    }
+
+    pub async fn prior_key(&self) -> Option<Event> {
+        self.history.read().await.last().cloned()
+    }
+
+    /// The key set sequence in progress, if any.
+    pub async fn pending(&self) -> Option<Pending> {
+        self.pending.read().await.clone()
+    }
+
+    /// Key starting leader bindings, `Backslash` by default. Set it before
+    /// registering them, registered bindings keep the leader they had.
+    pub fn leader(&mut self, leader: BindKey) -> Self {
+        self.loaded_mut().settings.leader = leader;
+        self.to_owned()
+    }
+
+    /// Maximum gap after the leader and between the following steps.
+    pub fn leader_timeout(&mut self, timeout: Duration) -> Self {
+        self.loaded_mut().settings.leader_timeout = timeout;
+        self.to_owned()
+    }
+
+    /// The key set of a leader binding, the leader followed by `sequence`
+    /// such as `"f s"`.
+    pub fn leader_key_set(&self, sequence: &str) -> Result<KeySet> {
+        let steps = sequence.parse::<KeySet>()?.bind_keys;
+        let settings = self.settings();
+        let mut key_set = KeySet::from(settings.leader);
+        for bind_key in steps {
+            if let Some(last) = key_set.bind_keys.last_mut() {
+                last.delay_time = settings.leader_timeout;
+            }
+            key_set = key_set.bind(bind_key);
+        }
+        Ok(key_set)
+    }
+
+    /// Registers a Vim style leader binding, e.g. `"f s"` for the leader
+    /// then `F` then `S`.
+    pub async fn register_leader(&self, sequence: &str, callback: Hook) -> Result<()> {
+        self.register(self.leader_key_set(sequence)?, callback)
+            .await
+    }
+
+    pub async fn unregister_leader(&self, sequence: &str) -> Result<()> {
+        self.unregister(self.leader_key_set(sequence)?).await;
+        Ok(())
+    }
+
+    /// Stops running hooks until [`Listener::resume`]. History and the
+    /// pressed state are still tracked; a sequence in progress is dropped.
+    pub async fn pause(&self) {
+        if !self.paused.swap(true, Ordering::SeqCst) {
+            cancel_pending(self, CancelReason::Paused).await;
+            self.notifier.notify(Notification::Paused(true));
+        }
+    }
+
+    pub async fn resume(&self) {
+        if self.paused.swap(false, Ordering::SeqCst) {
+            self.notifier.notify(Notification::Paused(false));
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Key set toggling [`Listener::pause`], it works while paused and never
+    /// reaches hooks. It holds across profiles.
+    pub fn kill_switch(&mut self, key_set: Option<KeySet>) -> Self {
+        *self
+            .kill_switch
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = key_set;
+        self.to_owned()
+    }
+
+    pub(crate) fn kill_switch_key_set(&self) -> Option<KeySet> {
+        self.kill_switch
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Name of the loaded profile, `"default"` unless switched.
+    pub fn profile(&self) -> String {
+        self.loaded().name.clone()
+    }
+
+    /// Stores `profile` to switch to later, replacing one of the same name.
+    /// Adding the loaded profile reloads it. Fails, keeping the profiles as
+    /// they are, if any of its bindings is invalid.
+    pub async fn add_profile(&self, profile: Profile) -> Result<()> {
+        for binding in &profile.bindings {
+            binding.trigger.validate()?;
+        }
+        let mut profiles = self.profiles.write().await;
+        let mut loaded = self.loaded_mut();
+        if loaded.name == profile.name {
+            *loaded = profile;
         } else {
-            self.hooks.write().await.push((key_set, callback));
-            Ok(())
+            profiles.insert(profile.name.clone(), profile);
         }
+        Ok(())
     }
 
-    pub async fn unregister(&self, key_set: KeySet) {
-        let mut hooks = self.hooks.write().await;
-        for i in 0..hooks.len() {
-            if hooks[i].0 == key_set {
-                hooks.remove(i);
-            }
+    /// Names of the profiles that can be switched to, the loaded one
+    /// included.
+    pub async fn profiles(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.profiles.read().await.keys().cloned().collect();
+        names.push(self.profile());
+        names.sort();
+        names
+    }
+
+    /// Loads the bindings and settings of profile `name` in one step, keeping
+    /// the previous profile, with changes made since, to switch back to. A
+    /// sequence in progress is cancelled.
+    pub async fn switch_profile(&self, name: &str) -> Result<()> {
+        let mut profiles = self.profiles.write().await;
+        if self.profile() == name {
+            return Ok(());
         }
+        let next = profiles
+            .remove(name)
+            .ok_or_else(|| Error::UnknownProfile(name.to_owned()))?;
+        let previous = std::mem::replace(&mut *self.loaded_mut(), next);
+        let from = previous.name.clone();
+        profiles.insert(previous.name.clone(), previous);
+        drop(profiles);
+
+        cancel_pending(self, CancelReason::ProfileSwitch).await;
+        self.notifier.notify(Notification::ProfileSwitched {
+            from,
+            to: name.to_owned(),
+        });
+        Ok(())
     }
 
-    pub async fn prior_key(&self) -> Option<Event> {
-        self.history.read().await.last().cloned()
+    /// Resets the sequence in progress, returning whether there was one.
+    pub async fn cancel(&self) -> bool {
+        cancel_pending(self, CancelReason::Manual).await
+    }
+
+    /// Streams [`Notification`]s such as sequence progress, e.g. to show the
+    /// possible continuations of a sequence.
+    pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+        self.notifier.subscribe()
     }
 
     pub fn listen(&self) -> JoinHandle<()> {
-        tokio::spawn(run(
-            Arc::clone(&self.history),
-            Arc::clone(&self.hooks),
-            self.max_history.clone(),
-        ))
+        tokio::spawn(run(self.clone()))
     }
 }
 
-pub async fn run(
-    history_arc: Arc<RwLock<History>>,
-    hooks: Arc<RwLock<Vec<(KeySet, Hook)>>>,
-    max_history: usize,
-) {
+pub async fn run(listener: Listener) {
     tokio::spawn(async move {
         match listen(move |event| {
-            tokio::spawn(handle_event(
-                event,
-                Arc::clone(&history_arc),
-                Arc::clone(&hooks),
-                max_history,
-            ));
+            tokio::spawn(handle_event(event.into(), listener.clone()));
         }) {
             Ok(_) => {}
             Err(e) => {
@@ -89,3 +439,221 @@ pub async fn run(
         }
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use futures::FutureExt;
+
+    use super::*;
+    use crate::{
+        event::{Edge, EventKind},
+        hooks::hook,
+    };
+
+    fn noop(_: HookContext) -> HookResult {
+        async move { Ok(()) }.boxed()
+    }
+
+    #[tokio::test]
+    async fn test_mode_bindings() {
+        let listener = Listener::default();
+        let enter_resize = Binding::new("Alt+R".parse::<KeySet>().unwrap(), noop)
+            .switch_mode(ModeChange::Push("resize".into()));
+        listener.register_binding(enter_resize).await.unwrap();
+        listener
+            .register_in_mode("resize", "H".parse::<KeySet>().unwrap(), noop)
+            .await
+            .unwrap();
+        assert_eq!(listener.active_bindings().await.len(), 1);
+
+        let history = History::from(vec![
+            Event::new(EventKind::Key(Key::KeyLeftAlt, Edge::Press)),
+            Event::new(EventKind::Key(Key::KeyR, Edge::Press)),
+        ]);
+        let bindings = listener.active_bindings().await;
+        hook(
+            history.clone(),
+            Arc::new(RwLock::new(history)),
+            bindings,
+            listener.modes.clone(),
+            Arc::clone(&listener.paused),
+            Arc::clone(&listener.events),
+        )
+        .await;
+        assert_eq!(listener.mode().await, Some("resize".into()));
+        assert_eq!(listener.active_bindings().await.len(), 2);
+
+        listener.toggle_mode("resize").await;
+        assert_eq!(listener.mode().await, None);
+    }
+
+    #[test]
+    fn test_leader_key_set() {
+        let listener = Listener::default()
+            .leader(BindKey::new(vec![Key::KeySpace]))
+            .leader_timeout(Duration::from_millis(500));
+        let key_set = listener.leader_key_set("f s").unwrap();
+        assert_eq!(
+            key_set,
+            KeySet::default()
+                .bind(BindKey::new(vec![Key::KeySpace]).delay(Duration::from_millis(500)))
+                .bind(BindKey::new(vec![Key::KeyF]).delay(Duration::from_millis(500)))
+                .bind(BindKey::new(vec![Key::KeyS]))
+        );
+        assert!(key_set.validate().is_ok());
+        assert!(listener.leader_key_set(" ").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_binding_groups() {
+        let listener = Listener::default();
+        listener
+            .register_in_group("gaming", "W".parse::<KeySet>().unwrap(), noop)
+            .await
+            .unwrap();
+        listener
+            .register("Ctrl+Q".parse::<KeySet>().unwrap(), noop)
+            .await
+            .unwrap();
+        assert_eq!(listener.active_bindings().await.len(), 2);
+
+        listener.disable_group("gaming").await;
+        assert!(!listener.is_group_enabled("gaming").await);
+        let active = listener.active_bindings().await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(active[0].group, None);
+
+        listener.enable_group("gaming").await;
+        assert_eq!(listener.active_bindings().await.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_switch_profile() {
+        let listener = Listener::default();
+        listener
+            .register("Ctrl+S".parse::<KeySet>().unwrap(), noop)
+            .await
+            .unwrap();
+        let left_handed = Profile::new("left-handed")
+            .bind(Binding::new("Ctrl+L".parse::<KeySet>().unwrap(), noop))
+            .bind(Binding::new("Ctrl+K".parse::<KeySet>().unwrap(), noop))
+            .settings(Settings {
+                cancel_key: Some(Key::KeyGrave),
+                ..Default::default()
+            });
+        listener.add_profile(left_handed).await.unwrap();
+        assert_eq!(listener.profiles().await, vec!["default", "left-handed"]);
+        let mut notifications = listener.subscribe();
+
+        assert!(listener.switch_profile("presentation").await.is_err());
+        listener.switch_profile("left-handed").await.unwrap();
+        assert_eq!(listener.profile(), "left-handed");
+        assert_eq!(listener.active_bindings().await.len(), 2);
+        assert_eq!(listener.settings().cancel_key, Some(Key::KeyGrave));
+        assert_eq!(
+            notifications.recv().await,
+            Ok(Notification::ProfileSwitched {
+                from: "default".into(),
+                to: "left-handed".into()
+            })
+        );
+
+        listener.switch_profile("default").await.unwrap();
+        assert_eq!(listener.active_bindings().await.len(), 1);
+        assert_eq!(listener.settings(), Settings::default());
+    }
+
+    #[tokio::test]
+    async fn test_add_invalid_profile() {
+        let listener = Listener::default();
+        listener
+            .register("Ctrl+S".parse::<KeySet>().unwrap(), noop)
+            .await
+            .unwrap();
+        let trailing_delay = KeySet::default().bind(
+            "Ctrl+T"
+                .parse::<BindKey>()
+                .unwrap()
+                .delay(Duration::from_millis(500)),
+        );
+        for key_set in [KeySet::default(), trailing_delay] {
+            for name in ["default", "broken"] {
+                let profile = Profile::new(name)
+                    .bind(Binding::new("Ctrl+L".parse::<KeySet>().unwrap(), noop))
+                    .bind(Binding::new(key_set.clone(), noop));
+                assert!(listener.add_profile(profile).await.is_err());
+            }
+        }
+        assert_eq!(listener.profiles().await, vec!["default"]);
+        let active = listener.active_bindings().await;
+        assert_eq!(active.len(), 1);
+        assert_eq!(
+            active[0].trigger,
+            "Ctrl+S".parse::<KeySet>().unwrap().into()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_pause_cancels_sequence() {
+        let listener = Listener::default();
+        let binding = Binding::new("Ctrl+K Ctrl+C".parse::<KeySet>().unwrap(), noop)
+            .switch_mode(ModeChange::Push("fired".into()));
+        listener.register_binding(binding).await.unwrap();
+        let press = |key| Event::new(EventKind::Key(key, Edge::Press));
+        for key in [Key::KeyLeftCtrl, Key::KeyK] {
+            handle_event(press(key), listener.clone()).await;
+        }
+        assert!(listener.pending().await.is_some());
+        let mut notifications = listener.subscribe();
+
+        listener.pause().await;
+        assert!(matches!(
+            notifications.recv().await,
+            Ok(Notification::SequenceCancelled(_, CancelReason::Paused))
+        ));
+        assert_eq!(listener.pending().await, None);
+        assert_eq!(listener.history.read().await.len(), 2);
+
+        // The cancelled sequence cannot be finished after resuming.
+        listener.resume().await;
+        for key in [Key::KeyLeftCtrl, Key::KeyC] {
+            handle_event(press(key), listener.clone()).await;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(listener.modes.current().await, None);
+    }
+
+    #[tokio::test]
+    async fn test_kill_switch() {
+        let mut listener = Listener::default();
+        // Clones share the kill switch, also when set afterwards.
+        let running = listener.clone();
+        listener.kill_switch(Some("Ctrl+Alt+P".parse::<KeySet>().unwrap()));
+        let listener = running;
+        let mut notifications = listener.subscribe();
+        let press = |key| Event::new(EventKind::Key(key, Edge::Press));
+
+        for key in [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyP] {
+            handle_event(press(key), listener.clone()).await;
+        }
+        assert!(listener.is_paused());
+        assert_eq!(notifications.recv().await, Ok(Notification::Paused(true)));
+        assert_eq!(listener.history.read().await.len(), 3);
+
+        // Still tracked while paused.
+        handle_event(press(Key::KeyA), listener.clone()).await;
+        assert_eq!(
+            listener
+                .prior_key()
+                .await
+                .and_then(|event| event.pressed_key()),
+            Some(Key::KeyA)
+        );
+
+        for key in [Key::KeyLeftCtrl, Key::KeyLeftAlt, Key::KeyP] {
+            handle_event(press(key), listener.clone()).await;
+        }
+        assert!(!listener.is_paused());
+        assert_eq!(notifications.recv().await, Ok(Notification::Paused(false)));
+    }
+}
@@ -1,7 +1,8 @@
 #![allow(unused)]
 use rshotkey::key::{BindKey, KeySet};
-use rshotkey::listener::{HookResult, Listener};
+use rshotkey::listener::{HookContext, HookResult, Listener};
 use rshotkey::rdev::{Button, EventType, Key};
+use rshotkey::trigger::{DualRole, Trigger};
 
 use anyhow::Result;
 use futures::FutureExt;
@@ -9,7 +10,7 @@ use std::process;
 use std::time::Duration;
 use tokio::time;
 
-fn clicked() -> HookResult {
+fn clicked(_: HookContext) -> HookResult {
     async move {
         println!("左键被按下");
         Ok(())
@@ -17,7 +18,7 @@ fn clicked() -> HookResult {
     .boxed()
 }
 
-fn press_c() -> HookResult {
+fn press_c(_: HookContext) -> HookResult {
     async move {
         println!("C被按下");
         Ok(())
@@ -25,7 +26,7 @@ fn press_c() -> HookResult {
     .boxed()
 }
 
-fn press_ctrl_d() -> HookResult {
+fn press_ctrl_d(_: HookContext) -> HookResult {
     async move {
         println!("Ctrl+D被按下");
         Ok(())
@@ -33,7 +34,39 @@ fn press_ctrl_d() -> HookResult {
     .boxed()
 }
 
-fn double_clicked() -> HookResult {
+fn shift_tapped(_: HookContext) -> HookResult {
+    async move {
+        println!("单独轻按Shift");
+        Ok(())
+    }
+    .boxed()
+}
+
+fn f_held(_: HookContext) -> HookResult {
+    async move {
+        println!("F被长按");
+        Ok(())
+    }
+    .boxed()
+}
+
+fn caps_lock_tapped(_: HookContext) -> HookResult {
+    async move {
+        println!("CapsLock轻按: Escape");
+        Ok(())
+    }
+    .boxed()
+}
+
+fn caps_lock_held(_: HookContext) -> HookResult {
+    async move {
+        println!("CapsLock长按: Ctrl");
+        Ok(())
+    }
+    .boxed()
+}
+
+fn double_clicked(_: HookContext) -> HookResult {
     async move {
         println!("鼠标双击");
         Ok(())
@@ -51,74 +84,67 @@ async fn main() -> Result<()> {
         EventType::KeyPress(Key::ControlLeft).into(),
     ])
     .into();
-    let ctrl_d_release = KeySet::default().bind(BindKey::new(vec![
-        EventType::KeyRelease(Key::KeyD).into(),
-        EventType::KeyRelease(Key::ControlLeft).into(),
-    ]));
-    let ctrl_d = KeySet::default()
+    let ctrl_k_ctrl_c = KeySet::default()
         .bind(BindKey::new(vec![
-            EventType::KeyPress(Key::KeyD).into(),
             EventType::KeyPress(Key::ControlLeft).into(),
+            EventType::KeyPress(Key::KeyK).into(),
         ]))
         .bind(BindKey::new(vec![
-            EventType::KeyRelease(Key::KeyD).into(),
-            EventType::KeyRelease(Key::ControlLeft).into(),
+            EventType::KeyPress(Key::ControlLeft).into(),
+            EventType::KeyPress(Key::KeyC).into(),
         ]));
 
-    let left_click_once: KeySet = BindKey::new(vec![EventType::ButtonPress(Button::Left).into()])
-        .delay(Duration::from_secs_f64(1.0))
-        .into();
+    let left_click_once = KeySet::from(BindKey::new(vec![
+        EventType::ButtonPress(Button::Left).into()
+    ]))
+    .settle(Duration::from_secs_f64(1.0));
 
-    let delay_click = KeySet::default()
-        .bind(
-            BindKey::new(vec![EventType::ButtonPress(Button::Left).into()])
-                .delay(Duration::from_secs_f64(0.2)),
-        )
-        .bind(BindKey::new(vec![EventType::ButtonRelease(Button::Left).into()]));
-
-    let double_click = KeySet::default()
-        .bind(
-            BindKey::new(vec![
-                EventType::ButtonPress(Button::Left).into(),
-                EventType::ButtonRelease(Button::Left).into(),
-            ])
-            .delay(Duration::from_secs_f64(0.2)),
-        )
-        .bind(BindKey::new(vec![EventType::ButtonPress(Button::Left).into()]));
+    let double_click = Trigger::tap_count(
+        rshotkey::key::Key::KeyMouseLeft,
+        2,
+        Duration::from_secs_f64(0.2),
+    );
 
     let listener = Listener::default();
     listener.register(c, press_c).await?;
     listener.register(ctrl_d_press, press_ctrl_d).await?;
     listener
-        .register(ctrl_d_release, move || {
+        .register(ctrl_k_ctrl_c, |_| {
             async move {
-                println!("Ctrl_D 被释放！");
+                println!("Ctrl+K Ctrl+C 被按下！");
                 Ok(())
             }
             .boxed()
         })
         .await?;
+
+    listener.register(left_click_once, clicked).await?;
+    listener.register(double_click, double_clicked).await?;
     listener
-        .register(ctrl_d, move || {
-            async move {
-                println!("完整的 Ctrl_D 过程！");
-                Ok(())
-            }
-            .boxed()
-        })
+        .register(
+            Trigger::tap_alone(rshotkey::key::Key::KeyShift, Duration::from_millis(300)),
+            shift_tapped,
+        )
         .await?;
+    listener
+        .register(
+            Trigger::hold(rshotkey::key::Key::KeyF, Duration::from_millis(800)),
+            f_held,
+        )
+        .await?;
+    let caps_lock = DualRole::new(rshotkey::key::Key::KeyCapsLock).permissive_hold(true);
+    listener.register(caps_lock.tap(), caps_lock_tapped).await?;
+    listener.register(caps_lock.hold(), caps_lock_held).await?;
 
-    listener.register(left_click_once, clicked).await?;
     listener
-        .register(delay_click, move || {
+        .register_leader("f s", |_| {
             async move {
-                println!("鼠标单击后在 0.2s 内释放！");
+                println!("Leader F S 被按下！");
                 Ok(())
             }
             .boxed()
         })
         .await?;
-    listener.register(double_click, double_clicked).await?;
 
     let runner = listener.listen();
 
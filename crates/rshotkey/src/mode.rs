@@ -0,0 +1,101 @@
+use std::sync::Arc;
+
+use tokio::sync::RwLock;
+
+use crate::notification::{Notification, Notifier};
+
+/// A change to the mode stack, applied when a binding fires.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ModeChange {
+    /// Enters the mode on top of the current one.
+    Push(String),
+    /// Goes back to the previous mode.
+    Pop,
+    /// Leaves the mode if it is the current one, enters it otherwise.
+    Toggle(String),
+}
+
+/// The stack of entered modes, the top one being current. Without any mode
+/// only bindings registered for every mode apply.
+#[derive(Debug, Clone)]
+pub struct Modes {
+    stack: Arc<RwLock<Vec<String>>>,
+    notifier: Notifier,
+}
+
+impl Modes {
+    pub(crate) fn new(notifier: Notifier) -> Self {
+        Self {
+            stack: Default::default(),
+            notifier,
+        }
+    }
+
+    pub async fn current(&self) -> Option<String> {
+        self.stack.read().await.last().cloned()
+    }
+
+    pub async fn stack(&self) -> Vec<String> {
+        self.stack.read().await.clone()
+    }
+
+    /// Applies `change`, notifying subscribers if the current mode changed.
+    pub async fn apply(&self, change: &ModeChange) {
+        let mut stack = self.stack.write().await;
+        let from = stack.last().cloned();
+        match change {
+            ModeChange::Push(mode) => stack.push(mode.clone()),
+            ModeChange::Pop => {
+                stack.pop();
+            }
+            ModeChange::Toggle(mode) if from.as_ref() == Some(mode) => {
+                stack.pop();
+            }
+            ModeChange::Toggle(mode) => stack.push(mode.clone()),
+        }
+        let to = stack.last().cloned();
+        drop(stack);
+
+        if from != to {
+            self.notifier.notify(Notification::ModeChanged { from, to });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_modes() {
+        let notifier = Notifier::new(16);
+        let mut notifications = notifier.subscribe();
+        let modes = Modes::new(notifier);
+        assert_eq!(modes.current().await, None);
+
+        modes.apply(&ModeChange::Push("resize".into())).await;
+        modes.apply(&ModeChange::Toggle("launch".into())).await;
+        assert_eq!(modes.stack().await, vec!["resize", "launch"]);
+        modes.apply(&ModeChange::Toggle("launch".into())).await;
+        assert_eq!(modes.current().await, Some("resize".into()));
+        modes.apply(&ModeChange::Pop).await;
+        // Popping an empty stack changes nothing.
+        modes.apply(&ModeChange::Pop).await;
+        assert_eq!(modes.current().await, None);
+
+        let mut changes = vec![];
+        while let Ok(Notification::ModeChanged { to, .. }) = notifications.try_recv() {
+            changes.push(to);
+        }
+        assert_eq!(
+            changes,
+            vec![
+                Some("resize".into()),
+                Some("launch".into()),
+                Some("resize".into()),
+                None
+            ]
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use tokio::sync::broadcast;
+
+use crate::resolver::Pending;
+
+/// Hands [`Notification`]s to every subscriber, shared by the listener
+/// and its modes.
+#[derive(Debug, Clone)]
+pub struct Notifier(broadcast::Sender<Notification>);
+
+impl Notifier {
+    /// Keeps up to `capacity` notifications for subscribers lagging behind.
+    pub fn new(capacity: usize) -> Self {
+        Self(broadcast::channel(capacity).0)
+    }
+
+    /// Sends `notification`, nobody listening is fine.
+    pub fn notify(&self, notification: Notification) {
+        let _ = self.0.send(notification);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<Notification> {
+        self.0.subscribe()
+    }
+}
+
+/// Changes in the listener state, see [`crate::listener::Listener::subscribe`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notification {
+    /// A sequence is in progress, sent on every press that advances it.
+    Pending(Pending),
+    /// The sequence in progress completed, broke off or ran past the gap
+    /// to its next step.
+    PendingCleared,
+    /// The sequence in progress was reset, it can no longer complete.
+    SequenceCancelled(Pending, CancelReason),
+    /// The current mode changed, `None` being outside of any mode.
+    ModeChanged {
+        from: Option<String>,
+        to: Option<String>,
+    },
+    /// Another profile was loaded.
+    ProfileSwitched { from: String, to: String },
+    /// Hooks stopped running, or ran again for `false`.
+    Paused(bool),
+}
+
+/// Why a sequence in progress was cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CancelReason {
+    /// The cancel key was pressed.
+    CancelKey,
+    /// The sequence ran past the listener's sequence timeout.
+    Timeout,
+    /// [`crate::listener::Listener::cancel`] was called.
+    Manual,
+    /// Another profile was loaded, the sequence belonged to the previous
+    /// keymap.
+    ProfileSwitch,
+    /// The listener was paused.
+    Paused,
+}
@@ -0,0 +1,59 @@
+use std::time::Duration;
+
+use crate::{
+    key::{BindKey, Key},
+    listener::Binding,
+};
+
+/// Listener settings that come with a [`Profile`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Settings {
+    /// Key that cancels a sequence in progress.
+    pub cancel_key: Option<Key>,
+    /// Time from the first press after which a sequence in progress is
+    /// cancelled, zero disables it.
+    pub sequence_timeout: Duration,
+    /// Key starting leader bindings.
+    pub leader: BindKey,
+    /// Maximum gap after the leader and between the following steps.
+    pub leader_timeout: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            cancel_key: Some(Key::KeyEscape),
+            sequence_timeout: Duration::ZERO,
+            leader: BindKey::new(vec![Key::KeyBackslash]),
+            leader_timeout: Duration::from_millis(1000),
+        }
+    }
+}
+
+/// A named keymap, its bindings and settings are loaded into a listener
+/// together, see [`crate::listener::Listener::switch_profile`].
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub name: String,
+    pub bindings: Vec<Binding>,
+    pub settings: Settings,
+}
+
+impl Profile {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ..Default::default()
+        }
+    }
+
+    pub fn bind(&mut self, binding: Binding) -> Self {
+        self.bindings.push(binding);
+        self.to_owned()
+    }
+
+    pub fn settings(&mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self.to_owned()
+    }
+}
@@ -0,0 +1,512 @@
+use std::time::{Duration, SystemTime};
+
+use crate::{
+    event::Event,
+    history::History,
+    hooks::{assign_unordered, capture_keyset},
+    key::{BindKey, Capture, Key, KeySet},
+    trigger::{match_hold_start, still_held, Trigger},
+};
+
+/// How long to wait for the next step of a longer binding whose matched
+/// step sets no delay.
+pub const CONTINUATION_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Outcome of resolving all registered key sets against the latest press.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Resolution {
+    /// No key set matched, or a timed trigger took the press.
+    Idle,
+    /// `key_set` matched and nothing longer can follow.
+    Fire {
+        key_set: KeySet,
+        captures: Vec<Capture>,
+    },
+    /// `key_set` matched, but a longer binding may still continue or the
+    /// match sets a settle time. It fires only if no other press arrives
+    /// within `timeout` and none of `claims` applies by then, a held claim
+    /// keeps it waiting while its key is down.
+    Wait {
+        key_set: KeySet,
+        captures: Vec<Capture>,
+        timeout: Duration,
+        claims: Vec<Claim>,
+    },
+}
+
+/// A timed trigger that takes the latest press over from a waiting key set,
+/// depending on whether the key is still down once the wait is over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Claim {
+    /// A hold fires if `key`, pressed at `since`, is still down after
+    /// `duration`. Released earlier, it no longer holds the key set back.
+    Held {
+        key: Key,
+        since: SystemTime,
+        duration: Duration,
+    },
+    /// A tap on its own fires if `key`, pressed at `since`, was released.
+    Released { key: Key, since: SystemTime },
+}
+
+impl Claim {
+    pub fn applies(&self, history: &History) -> bool {
+        match *self {
+            Claim::Held { key, since, .. } => still_held(key, since, history),
+            Claim::Released { key, since } => !still_held(key, since, history),
+        }
+    }
+
+    /// How long a held claim keeps the key set waiting while its key is down.
+    pub fn hold(&self) -> Option<Duration> {
+        match *self {
+            Claim::Held { duration, .. } => Some(duration),
+            Claim::Released { .. } => None,
+        }
+    }
+}
+
+/// Picks exactly one of `key_sets` for the latest presses in `history`.
+///
+/// The longest match wins, by steps and then by keys, earlier key sets
+/// first on ties. While the presses also match the first steps of a longer
+/// key set, or some of the keys of a combo it starts with, the winner is held
+/// back until that one can no longer continue.
+///
+/// The `timed` triggers of the other bindings take part as well. Tap counts
+/// count as key sets of their taps, winning means they fire on their own.
+/// The winner waits for a hold or a tap on its own of the latest press and
+/// is dropped if that happens, a dual-role key always takes the press.
+pub fn resolve(key_sets: &[KeySet], timed: &[Trigger], history: &History) -> Resolution {
+    // Behind the key sets, which win ties.
+    let candidates: Vec<KeySet> = key_sets
+        .iter()
+        .cloned()
+        .chain(timed.iter().filter_map(Trigger::as_key_set))
+        .collect();
+    let mut winner: Option<(usize, Vec<Capture>)> = None;
+    for (index, key_set) in candidates.iter().enumerate() {
+        let better = winner.as_ref().is_none_or(|(best, _)| {
+            let best = &candidates[*best];
+            (key_set.bind_keys.len(), key_set.len()) > (best.bind_keys.len(), best.len())
+        });
+        if better {
+            if let Some(captures) = capture_keyset(key_set, history) {
+                winner = Some((index, captures));
+            }
+        }
+    }
+    let Some((index, captures)) = winner else {
+        return Resolution::Idle;
+    };
+    if index >= key_sets.len() {
+        return Resolution::Idle;
+    }
+    let key_set = &candidates[index];
+
+    let mut claims = vec![];
+    let mut waits = vec![key_set.settle];
+    let pressed = history.last().and_then(Event::pressed_key);
+    for trigger in timed {
+        let key = match trigger {
+            Trigger::DualRole(dual_role, _)
+                if pressed.is_some_and(|key| dual_role.key.matches(key)) =>
+            {
+                return Resolution::Idle;
+            }
+            Trigger::Hold { key, .. } | Trigger::TapAlone { key, .. } => key,
+            _ => continue,
+        };
+        match match_hold_start(*key, history) {
+            Some((key, since)) => match trigger {
+                Trigger::Hold { duration, .. } => claims.push(Claim::Held {
+                    key,
+                    since,
+                    duration: *duration,
+                }),
+                Trigger::TapAlone { max_hold, .. } => {
+                    waits.push(*max_hold);
+                    claims.push(Claim::Released { key, since });
+                }
+                _ => {}
+            },
+            // Auto-repeat of a key being held.
+            None if matches!(trigger, Trigger::Hold { .. })
+                && pressed.is_some_and(|pressed| key.matches(pressed)) =>
+            {
+                return Resolution::Idle;
+            }
+            None => {}
+        }
+    }
+
+    let timeout = candidates
+        .iter()
+        .filter(|longer| longer.bind_keys.len() > key_set.bind_keys.len())
+        .flat_map(|longer| (1..longer.bind_keys.len()).map(move |steps| (longer, steps)))
+        .filter_map(|(longer, steps)| match_prefix(longer, steps, history))
+        .chain(
+            key_sets
+                .iter()
+                .filter(|combo| *combo != key_set)
+                .filter_map(|combo| match_combo_start(combo, history)),
+        )
+        .chain(waits)
+        .max()
+        .unwrap_or_default();
+
+    let key_set = key_set.clone();
+    if timeout.is_zero() && claims.is_empty() {
+        Resolution::Fire { key_set, captures }
+    } else {
+        Resolution::Wait {
+            key_set,
+            captures,
+            timeout,
+            claims,
+        }
+    }
+}
+
+/// Checks whether the latest presses match the first `steps` steps of
+/// `key_set`, returning how long to wait for the next step.
+pub fn match_prefix(key_set: &KeySet, steps: usize, history: &History) -> Option<Duration> {
+    let prefix = KeySet {
+        bind_keys: key_set.bind_keys.get(..steps)?.to_vec(),
+        not_after: key_set.not_after.clone(),
+        deadline: key_set.deadline,
+        ..Default::default()
+    };
+    capture_keyset(&prefix, history)?;
+    let delay = prefix.last()?.delay_time;
+    Some(if delay.is_zero() {
+        CONTINUATION_TIMEOUT
+    } else {
+        delay
+    })
+}
+
+/// Checks whether the latest presses are some, not all, of the keys of the
+/// combo `key_set` starts with, returning how much of its window is left.
+pub fn match_combo_start(key_set: &KeySet, history: &History) -> Option<Duration> {
+    let combo = key_set
+        .bind_keys
+        .first()
+        .filter(|bind_key| !bind_key.combo_time.is_zero())?;
+    let mut presses: Vec<(Key, SystemTime)> = history
+        .iter()
+        .rev()
+        .filter_map(|event| event.pressed_key().map(|key| (key, event.time)))
+        .take(combo.len().saturating_sub(1))
+        .collect();
+    presses.reverse();
+    let (_, last) = *presses.last()?;
+    (0..presses.len()).find_map(|start| {
+        let checks = &presses[start..];
+        let spread = last.duration_since(checks[0].1).unwrap_or_default();
+        let mut used = vec![false; combo.len()];
+        (spread <= combo.combo_time
+            && assign_unordered(combo.keys(), checks, &mut used, &mut vec![]))
+        .then(|| combo.combo_time - spread)
+    })
+}
+
+/// A key set the presses so far are the beginning of.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub key_set: KeySet,
+    /// Number of steps already matched.
+    pub matched: usize,
+}
+
+impl Candidate {
+    /// Steps still to be pressed.
+    pub fn remaining(&self) -> &[BindKey] {
+        &self.key_set.bind_keys[self.matched..]
+    }
+}
+
+/// A sequence in progress, for which-key style hints.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pending {
+    /// Events since the first press of the longest partial match.
+    pub events: Vec<Event>,
+    pub candidates: Vec<Candidate>,
+    /// When the sequence breaks off unless another step is pressed.
+    pub expires_at: SystemTime,
+}
+
+/// Finds the key sets that the latest presses are a proper beginning of and
+/// can still continue at `now`.
+pub fn find_pending(key_sets: &[KeySet], history: &History, now: SystemTime) -> Option<Pending> {
+    let last_press = history.last_press()?.time;
+    let mut expires_at = last_press;
+    let mut candidates = vec![];
+    for key_set in key_sets {
+        let longest = (1..key_set.bind_keys.len())
+            .rev()
+            .find_map(|steps| Some((steps, match_prefix(key_set, steps, history)?)));
+        if let Some((matched, timeout)) = longest {
+            if last_press + timeout >= now {
+                expires_at = expires_at.max(last_press + timeout);
+                candidates.push(Candidate {
+                    key_set: key_set.clone(),
+                    matched,
+                });
+            }
+        }
+    }
+
+    let presses = candidates
+        .iter()
+        .map(|candidate| {
+            candidate.key_set.bind_keys[..candidate.matched]
+                .iter()
+                .map(BindKey::len)
+                .sum::<usize>()
+        })
+        .max()?;
+    let first = history
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, event)| event.pressed_key().is_some())
+        .nth(presses - 1)?
+        .0;
+    Some(Pending {
+        events: history.iter().skip(first).cloned().collect(),
+        candidates,
+        expires_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        event::{Edge, EventKind},
+        key::{BindKey, Key},
+        trigger::DualRole,
+    };
+
+    fn presses(keys: &[Key]) -> History {
+        History::from(
+            keys.iter()
+                .map(|key| Event::new(EventKind::Key(*key, Edge::Press)).at(SystemTime::UNIX_EPOCH))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    #[test]
+    fn test_resolve_idle() {
+        let key_sets = vec![KeySet::from(BindKey::new(vec![Key::KeyA]))];
+        assert_eq!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyB])),
+            Resolution::Idle
+        );
+    }
+
+    #[test]
+    fn test_resolve_waits_for_longer_binding() {
+        let click = BindKey::new(vec![Key::KeyMouseLeft]);
+        let single = KeySet::from(click.clone());
+        let double = KeySet::default()
+            .bind(click.clone().delay(Duration::from_millis(200)))
+            .bind(click);
+        let key_sets = vec![single.clone(), double.clone()];
+
+        assert_eq!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyMouseLeft])),
+            Resolution::Wait {
+                key_set: single,
+                captures: vec![],
+                timeout: Duration::from_millis(200),
+                claims: vec![]
+            }
+        );
+        assert_eq!(
+            resolve(
+                &key_sets,
+                &[],
+                &presses(&[Key::KeyMouseLeft, Key::KeyMouseLeft])
+            ),
+            Resolution::Fire {
+                key_set: double,
+                captures: vec![]
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_prefers_more_keys() {
+        let c = KeySet::from(BindKey::new(vec![Key::KeyC]));
+        let ctrl_c = KeySet::from(BindKey::new(vec![Key::KeyCtrl, Key::KeyC]));
+        let key_sets = vec![c, ctrl_c.clone()];
+        assert_eq!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyLeftCtrl, Key::KeyC])),
+            Resolution::Fire {
+                key_set: ctrl_c,
+                captures: vec![Capture {
+                    class: Key::KeyCtrl,
+                    key: Key::KeyLeftCtrl
+                }]
+            }
+        );
+    }
+
+    #[test]
+    fn test_resolve_undelayed_prefix_uses_timeout() {
+        let ctrl_k = BindKey::new(vec![Key::KeyCtrl, Key::KeyK]);
+        let key_sets = vec![
+            KeySet::from(ctrl_k.clone()),
+            KeySet::default()
+                .bind(ctrl_k)
+                .bind(BindKey::new(vec![Key::KeyCtrl, Key::KeyC])),
+        ];
+        assert!(matches!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyLeftCtrl, Key::KeyK])),
+            Resolution::Wait {
+                timeout: CONTINUATION_TIMEOUT,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_resolve_tap_count() {
+        let click = KeySet::from(BindKey::new(vec![Key::KeyMouseLeft]));
+        let within = Duration::from_millis(200);
+        let key_sets = vec![click.clone()];
+        let timed = [Trigger::tap_count(Key::KeyMouseLeft, 2, within)];
+
+        assert_eq!(
+            resolve(&key_sets, &timed, &presses(&[Key::KeyMouseLeft])),
+            Resolution::Wait {
+                key_set: click,
+                captures: vec![],
+                timeout: within,
+                claims: vec![]
+            }
+        );
+        // The double click takes the second click, it fires on its own.
+        assert_eq!(
+            resolve(
+                &key_sets,
+                &timed,
+                &presses(&[Key::KeyMouseLeft, Key::KeyMouseLeft])
+            ),
+            Resolution::Idle
+        );
+    }
+
+    #[test]
+    fn test_resolve_timed_claims() {
+        let key_sets = vec![KeySet::from(BindKey::new(vec![Key::KeyF]))];
+        let duration = Duration::from_millis(800);
+        let history = presses(&[Key::KeyF]);
+        let Resolution::Wait {
+            timeout, claims, ..
+        } = resolve(&key_sets, &[Trigger::hold(Key::KeyF, duration)], &history)
+        else {
+            panic!("expected the key set to wait for the hold");
+        };
+        // Only the hold keeps it waiting, for as long as F is down.
+        assert_eq!(timeout, Duration::ZERO);
+        assert_eq!(claims[0].hold(), Some(duration));
+        assert!(claims[0].applies(&history));
+
+        let mut released = history.clone();
+        released
+            .push(Event::new(EventKind::Key(Key::KeyF, Edge::Release)).at(SystemTime::UNIX_EPOCH));
+        assert!(!claims[0].applies(&released));
+
+        let dual_role = DualRole::new(Key::KeyF);
+        assert_eq!(
+            resolve(&key_sets, &[dual_role.tap()], &history),
+            Resolution::Idle
+        );
+    }
+
+    #[test]
+    fn test_resolve_waits_for_combo() {
+        let j = KeySet::from(BindKey::new(vec![Key::KeyJ]));
+        let j_k =
+            KeySet::from(BindKey::new(vec![Key::KeyJ, Key::KeyK]).combo(Duration::from_millis(50)));
+        let key_sets = vec![j.clone(), j_k.clone()];
+
+        assert_eq!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyJ])),
+            Resolution::Wait {
+                key_set: j,
+                captures: vec![],
+                timeout: Duration::from_millis(50),
+                claims: vec![]
+            }
+        );
+        // The combo replaces its member keys once complete.
+        assert_eq!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyJ, Key::KeyK])),
+            Resolution::Fire {
+                key_set: j_k,
+                captures: vec![]
+            }
+        );
+        assert!(matches!(
+            resolve(&key_sets, &[], &presses(&[Key::KeyK, Key::KeyJ])),
+            Resolution::Fire { .. }
+        ));
+    }
+
+    #[test]
+    fn test_find_pending() {
+        let ctrl_k = BindKey::new(vec![Key::KeyCtrl, Key::KeyK]);
+        let ctrl_k_ctrl_c = KeySet::default()
+            .bind(ctrl_k.clone())
+            .bind(BindKey::new(vec![Key::KeyCtrl, Key::KeyC]));
+        let ctrl_k_x_y = KeySet::default()
+            .bind(ctrl_k.clone().delay(Duration::from_millis(500)))
+            .bind(BindKey::new(vec![Key::KeyX]).delay(Duration::from_millis(500)))
+            .bind(BindKey::new(vec![Key::KeyY]));
+        let key_sets = vec![
+            KeySet::from(ctrl_k),
+            ctrl_k_ctrl_c.clone(),
+            ctrl_k_x_y.clone(),
+        ];
+
+        let history = presses(&[Key::KeyA, Key::KeyLeftCtrl, Key::KeyK]);
+        let pending = find_pending(&key_sets, &history, SystemTime::UNIX_EPOCH).unwrap();
+        assert_eq!(pending.events, history.last_n(2));
+        assert_eq!(
+            pending.expires_at,
+            SystemTime::UNIX_EPOCH + CONTINUATION_TIMEOUT
+        );
+        assert_eq!(
+            pending.candidates,
+            vec![
+                Candidate {
+                    key_set: ctrl_k_ctrl_c,
+                    matched: 1
+                },
+                Candidate {
+                    key_set: ctrl_k_x_y.clone(),
+                    matched: 1
+                },
+            ]
+        );
+        assert_eq!(
+            pending.candidates[1].remaining(),
+            &ctrl_k_x_y.bind_keys[1..]
+        );
+
+        // Only the key set with the longer timeout is still waiting.
+        let later = SystemTime::UNIX_EPOCH + Duration::from_millis(600);
+        assert_eq!(
+            find_pending(&key_sets, &history, later)
+                .unwrap()
+                .candidates
+                .len(),
+            1
+        );
+        assert!(find_pending(&key_sets, &presses(&[Key::KeyK]), SystemTime::UNIX_EPOCH).is_none());
+    }
+}
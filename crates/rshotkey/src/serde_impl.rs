@@ -0,0 +1,174 @@
+use std::time::Duration;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    event::Event,
+    history::History,
+    key::{BindKey, ChordOrder, Key, DEFAULT_MAX_COUNT},
+};
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        name.parse().map_err(de::Error::custom)
+    }
+}
+
+/// Deserialized form of [`BindKey`], normalized on conversion.
+#[derive(Deserialize)]
+pub(crate) struct RawBindKey {
+    keys: Vec<Key>,
+    #[serde(default, with = "duration_ms")]
+    delay_time: Duration,
+    #[serde(default, with = "duration_ms")]
+    combo_time: Duration,
+    #[serde(default)]
+    order: ChordOrder,
+    #[serde(default)]
+    without: Vec<Key>,
+}
+
+impl From<RawBindKey> for BindKey {
+    fn from(value: RawBindKey) -> Self {
+        BindKey {
+            keys: BindKey::normalize(value.keys, value.order),
+            delay_time: value.delay_time,
+            combo_time: value.combo_time,
+            order: value.order,
+            without: BindKey::normalize(value.without, ChordOrder::Unordered),
+        }
+    }
+}
+
+/// Deserialized form of [`History`], whose pressed keys are rebuilt from the
+/// events on conversion.
+#[derive(Deserialize)]
+pub(crate) struct RawHistory {
+    capacity: usize,
+    entries: Vec<Event>,
+}
+
+impl From<RawHistory> for History {
+    fn from(value: RawHistory) -> Self {
+        let mut history = History::new(value.capacity);
+        value
+            .entries
+            .into_iter()
+            .for_each(|event| history.push(event));
+        history
+    }
+}
+
+pub(crate) fn default_max_count() -> u32 {
+    DEFAULT_MAX_COUNT
+}
+
+/// Represents a [`Duration`] as whole milliseconds.
+pub(crate) mod duration_ms {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(duration.as_millis().try_into().unwrap_or(u64::MAX))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+        Ok(Duration::from_millis(u64::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::{
+        event::{Edge, Event, EventKind},
+        history::History,
+        key::{BindKey, KeySet},
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_key_serde() {
+        assert_eq!(
+            serde_json::to_value(Key::KeyLeftCtrl).unwrap(),
+            json!("LeftCtrl")
+        );
+        assert_eq!(
+            serde_json::from_value::<Key>(json!("esc")).unwrap(),
+            Key::KeyEscape
+        );
+        assert!(serde_json::from_value::<Key>(json!("Hyper")).is_err());
+    }
+
+    #[test]
+    fn test_key_set_serde() {
+        let key_set = KeySet::default()
+            .bind(BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyK]).delay(Duration::from_millis(500)))
+            .bind(BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyC]))
+            .settle(Duration::from_millis(100));
+        let value = serde_json::to_value(&key_set).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "bind_keys": [
+                    { "keys": ["LeftCtrl", "K"], "delay_time": 500, "combo_time": 0, "order": "Unordered", "without": [] },
+                    { "keys": ["LeftCtrl", "C"], "delay_time": 0, "combo_time": 0, "order": "Unordered", "without": [] },
+                ],
+                "modifier_policy": "Subset",
+                "not_after": [],
+                "deadline": 0,
+                "settle": 100,
+                "count_prefix": "Off",
+                "max_count": 100,
+            })
+        );
+        assert_eq!(serde_json::from_value::<KeySet>(value).unwrap(), key_set);
+    }
+
+    #[test]
+    fn test_bind_key_deserialize_defaults() {
+        let bind_key: BindKey =
+            serde_json::from_value(json!({ "keys": ["LeftCtrl", "K"] })).unwrap();
+        assert_eq!(bind_key, BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyK]));
+        let value = serde_json::to_value(&bind_key).unwrap();
+        assert_eq!(serde_json::from_value::<BindKey>(value).unwrap(), bind_key);
+    }
+
+    #[test]
+    fn test_bind_key_deserialize_normalizes() {
+        let bind_key: BindKey =
+            serde_json::from_value(json!({ "keys": ["D", "LeftCtrl", "D"], "delay_time": 0 }))
+                .unwrap();
+        assert_eq!(bind_key, BindKey::new(vec![Key::KeyLeftCtrl, Key::KeyD]));
+
+        let bind_key: BindKey = serde_json::from_value(
+            json!({ "keys": ["S", "LeftCtrl"], "delay_time": 0, "order": "Ordered" }),
+        )
+        .unwrap();
+        assert_eq!(
+            bind_key,
+            BindKey::ordered(vec![Key::KeyS, Key::KeyLeftCtrl])
+        );
+    }
+
+    #[test]
+    fn test_history_serde() {
+        let mut history = History::new(16);
+        history.push(Event::new(EventKind::Key(Key::KeyA, Edge::Press)));
+        history.push(Event::new(EventKind::Move { x: 1.0, y: 2.0 }));
+        let json = serde_json::to_string(&history).unwrap();
+        let restored: History = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(restored.last(), history.last());
+        assert!(restored.is_pressed(Key::KeyA));
+        assert_eq!(restored.boundary(), 0);
+    }
+}
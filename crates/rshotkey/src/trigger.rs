@@ -0,0 +1,609 @@
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+
+use crate::{
+    event::{Edge, Event},
+    exception::Error,
+    history::History,
+    key::{BindKey, Capture, Key, KeySet},
+};
+
+/// What a hook is registered for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Trigger {
+    /// A sequence of chords.
+    KeySet(KeySet),
+    /// Fires when `key` is released, provided no other key was pressed while
+    /// it was held and it was held for at most `max_hold`.
+    TapAlone {
+        key: Key,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::duration_ms"))]
+        max_hold: Duration,
+    },
+    /// Fires once `key` has been held for `duration`, then every `repeat`
+    /// while it stays down. A zero `repeat` fires only once.
+    Hold {
+        key: Key,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::duration_ms"))]
+        duration: Duration,
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, with = "crate::serde_impl::duration_ms")
+        )]
+        repeat: Duration,
+    },
+    /// One role of a tap-or-hold key.
+    DualRole(DualRole, Role),
+    /// Fires when `key` is tapped exactly `count` times in a row, each tap
+    /// within `within` of the previous one. It waits `within` after the last
+    /// tap, so a triple tap does not fire the double tap as well.
+    TapCount {
+        key: Key,
+        count: usize,
+        #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::duration_ms"))]
+        within: Duration,
+    },
+}
+
+impl Trigger {
+    /// Taps such as "Shift alone toggles the input mode".
+    pub fn tap_alone(key: Key, max_hold: Duration) -> Self {
+        Trigger::TapAlone { key, max_hold }
+    }
+
+    pub fn hold(key: Key, duration: Duration) -> Self {
+        Trigger::Hold {
+            key,
+            duration,
+            repeat: Duration::ZERO,
+        }
+    }
+
+    /// Like [`Trigger::hold`], firing again every `repeat` until released.
+    pub fn hold_repeat(key: Key, duration: Duration, repeat: Duration) -> Self {
+        Trigger::Hold {
+            key,
+            duration,
+            repeat,
+        }
+    }
+
+    /// Double or triple taps of a key or mouse button.
+    pub fn tap_count(key: Key, count: usize, within: Duration) -> Self {
+        Trigger::TapCount { key, count, within }
+    }
+
+    /// The key set pressing the same keys, so key sets and tap counts can be
+    /// resolved against each other. `None` for triggers that depend on how
+    /// long a key is held.
+    pub fn as_key_set(&self) -> Option<KeySet> {
+        match self {
+            Trigger::KeySet(key_set) => Some(key_set.clone()),
+            Trigger::TapCount { key, count, within } => {
+                let tap = BindKey::new(vec![*key]);
+                let mut key_set = KeySet::default();
+                for _ in 1..*count {
+                    key_set = key_set.bind(tap.clone().delay(*within));
+                }
+                Some(key_set.bind(tap))
+            }
+            Trigger::TapAlone { .. } | Trigger::Hold { .. } | Trigger::DualRole(..) => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Trigger::KeySet(key_set) => key_set.is_empty(),
+            Trigger::TapCount { count, .. } => *count == 0,
+            Trigger::TapAlone { .. } | Trigger::Hold { .. } | Trigger::DualRole(..) => false,
+        }
+    }
+
+    /// Rejects triggers that could never fire, see [`KeySet::validate`].
+    pub fn validate(&self) -> Result<()> {
+        match self {
+            Trigger::KeySet(key_set) => key_set.validate(),
+            _ if self.is_empty() => Err(Error::EmptyHotkey.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Which role of a [`DualRole`] key a trigger stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Role {
+    Tap,
+    Hold,
+}
+
+/// A key doing one thing when tapped and another when held, e.g. CapsLock as
+/// Escape on tap and Ctrl on hold. Register both [`DualRole::tap`] and
+/// [`DualRole::hold`], exactly one of them fires per press.
+///
+/// The key resolves to a hold once held for `tapping_term`, otherwise to a
+/// tap on release. With `permissive_hold`, tapping another key while it is
+/// held resolves it to a hold right away.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DualRole {
+    pub key: Key,
+    #[cfg_attr(feature = "serde", serde(with = "crate::serde_impl::duration_ms"))]
+    pub tapping_term: Duration,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub permissive_hold: bool,
+}
+
+impl DualRole {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key,
+            tapping_term: Duration::from_millis(200),
+            permissive_hold: false,
+        }
+    }
+
+    pub fn tapping_term(&mut self, tapping_term: Duration) -> Self {
+        self.tapping_term = tapping_term;
+        self.to_owned()
+    }
+
+    pub fn permissive_hold(&mut self, permissive_hold: bool) -> Self {
+        self.permissive_hold = permissive_hold;
+        self.to_owned()
+    }
+
+    pub fn tap(&self) -> Trigger {
+        Trigger::DualRole(self.clone(), Role::Tap)
+    }
+
+    pub fn hold(&self) -> Trigger {
+        Trigger::DualRole(self.clone(), Role::Hold)
+    }
+}
+
+impl From<KeySet> for Trigger {
+    fn from(value: KeySet) -> Self {
+        Trigger::KeySet(value)
+    }
+}
+
+/// Checks whether the latest event releases `key` after it was tapped on its
+/// own, returning the concrete key released.
+pub fn match_tap_alone(key: Key, max_hold: Duration, history: &History) -> Option<Capture> {
+    let release = history.last()?;
+    let released = release.key()?;
+    if release.edge() != Some(Edge::Release) || !key.matches(released) {
+        return None;
+    }
+
+    let events = &history.last_n(history.len())[..history.len() - 1];
+    let start = hold_start(released, events)?;
+    // Any other press while it was held means it was used as a modifier.
+    if events[start..]
+        .iter()
+        .any(|event| event.pressed_key().is_some_and(|key| key != released))
+    {
+        return None;
+    }
+    let pressed_at = events[start].time;
+
+    let held = release.time.duration_since(pressed_at).unwrap_or_default();
+    (held <= max_hold).then_some(Capture {
+        class: key,
+        key: released,
+    })
+}
+
+/// Index of the press that started the current hold of `key` in `events`,
+/// skipping auto-repeat back to the previous release.
+fn hold_start(key: Key, events: &[Event]) -> Option<usize> {
+    let mut start = None;
+    for (index, event) in events.iter().enumerate().rev() {
+        if event.key() == Some(key) {
+            match event.edge() {
+                Some(Edge::Press) => start = Some(index),
+                _ => break,
+            }
+        }
+    }
+    start
+}
+
+/// Index of the first release of another key that was both pressed and
+/// released in `events`, the first of which starts the hold of `key`.
+fn interruption(key: Key, events: &[Event]) -> Option<usize> {
+    let mut others = Vec::new();
+    for (index, event) in events.iter().enumerate().skip(1) {
+        match (event.key(), event.edge()) {
+            (Some(other), Some(Edge::Press)) if other != key => others.push(other),
+            (Some(other), Some(Edge::Release)) if others.contains(&other) => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Checks what the latest event resolves a dual-role key to, if anything:
+/// a tap on an early release, or a hold once another key is tapped inside
+/// the tapping term under permissive hold. Timeouts are left to
+/// [`resolve_dual_role_timeout`].
+pub fn resolve_dual_role(dual_role: &DualRole, history: &History) -> Option<(Role, Capture)> {
+    let last = history.last()?;
+    let key = last.key()?;
+    if last.edge() != Some(Edge::Release) {
+        return None;
+    }
+    let events = history.last_n(history.len());
+
+    if dual_role.key.matches(key) {
+        let start = hold_start(key, &events[..events.len() - 1])?;
+        let held = last
+            .time
+            .duration_since(events[start].time)
+            .unwrap_or_default();
+        let resolved_hold =
+            dual_role.permissive_hold && interruption(key, &events[start..]).is_some();
+        return (held < dual_role.tapping_term && !resolved_hold).then_some((
+            Role::Tap,
+            Capture {
+                class: dual_role.key,
+                key,
+            },
+        ));
+    }
+
+    if !dual_role.permissive_hold {
+        return None;
+    }
+    history
+        .pressed()
+        .iter()
+        .filter(|pressed| dual_role.key.matches(**pressed))
+        .find_map(|pressed| {
+            let start = hold_start(*pressed, events)?;
+            let held = last
+                .time
+                .duration_since(events[start].time)
+                .unwrap_or_default();
+            (held < dual_role.tapping_term
+                && interruption(*pressed, &events[start..]).map(|index| start + index)
+                    == Some(events.len() - 1))
+            .then_some((
+                Role::Hold,
+                Capture {
+                    class: dual_role.key,
+                    key: *pressed,
+                },
+            ))
+        })
+}
+
+/// Whether a dual-role key pressed at `since` resolves to a hold once the
+/// tapping term has run out.
+pub fn resolve_dual_role_timeout(
+    dual_role: &DualRole,
+    key: Key,
+    since: SystemTime,
+    history: &History,
+) -> bool {
+    if !still_held(key, since, history) {
+        return false;
+    }
+    // Under permissive hold an earlier interruption already resolved it.
+    let events = history.last_n(history.len());
+    let resolved = hold_start(key, events)
+        .and_then(|start| {
+            interruption(key, &events[start..]).map(|index| events[start + index].time)
+        })
+        .is_some_and(|time| time < since + dual_role.tapping_term);
+    !(dual_role.permissive_hold && resolved)
+}
+
+/// Presses in `history` that are not auto-repeat, oldest first.
+fn fresh_presses(history: &History) -> Vec<&Event> {
+    let mut down = Vec::new();
+    history
+        .iter()
+        .filter(|event| match (event.key(), event.edge()) {
+            (Some(key), Some(Edge::Press)) if !down.contains(&key) => {
+                down.push(key);
+                true
+            }
+            (Some(key), Some(Edge::Release)) => {
+                down.retain(|pressed| *pressed != key);
+                false
+            }
+            _ => false,
+        })
+        .collect()
+}
+
+/// Checks whether the latest event is the `count`-th tap in a row of `key`,
+/// returning the concrete key and when it went down. Whether the run goes on
+/// is left to [`tap_run_extended`].
+pub fn match_tap_count(
+    key: Key,
+    count: usize,
+    within: Duration,
+    history: &History,
+) -> Option<(Key, SystemTime)> {
+    let last = history.last()?;
+    let presses = fresh_presses(history);
+    let (&latest, earlier) = presses.split_last()?;
+    let pressed = latest
+        .pressed_key()
+        .filter(|pressed| key.matches(*pressed))?;
+    if !std::ptr::eq(latest, last) {
+        return None;
+    }
+
+    let mut run = 1;
+    let mut time = latest.time;
+    for event in earlier.iter().rev() {
+        let gap = time.duration_since(event.time).unwrap_or_default();
+        if event.pressed_key() != Some(pressed) || gap > within {
+            break;
+        }
+        run += 1;
+        time = event.time;
+    }
+    (run == count).then_some((pressed, latest.time))
+}
+
+/// Whether `key` was tapped again after the tap at `since`.
+pub fn tap_run_extended(key: Key, since: SystemTime, history: &History) -> bool {
+    fresh_presses(history)
+        .iter()
+        .any(|event| event.time > since && event.pressed_key() == Some(key))
+}
+
+/// Checks whether the latest event is a fresh press of `key`, ignoring
+/// auto-repeat, returning the concrete key and when it went down.
+pub fn match_hold_start(key: Key, history: &History) -> Option<(Key, SystemTime)> {
+    let press = history.last()?;
+    let pressed = press
+        .pressed_key()
+        .filter(|pressed| key.matches(*pressed))?;
+    let repeated = history
+        .iter()
+        .rev()
+        .skip(1)
+        .find(|event| event.key() == Some(pressed))
+        .is_some_and(|event| event.edge() == Some(Edge::Press));
+    (!repeated).then_some((pressed, press.time))
+}
+
+/// Whether `key` is still down from the press at `since`.
+pub fn still_held(key: Key, since: SystemTime, history: &History) -> bool {
+    history.pressed().contains(&key)
+        && !history.iter().rev().any(|event| {
+            event.time >= since && event.edge() == Some(Edge::Release) && event.key() == Some(key)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::EventKind;
+
+    fn key_event(key: Key, edge: Edge, millis: u64) -> Event {
+        Event::new(EventKind::Key(key, edge))
+            .at(SystemTime::UNIX_EPOCH + Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn test_match_tap_alone() {
+        let max_hold = Duration::from_millis(300);
+        let history = History::from(vec![
+            key_event(Key::KeyA, Edge::Press, 0),
+            key_event(Key::KeyA, Edge::Release, 10),
+            key_event(Key::KeyLeftShift, Edge::Press, 100),
+            key_event(Key::KeyLeftShift, Edge::Press, 150),
+            key_event(Key::KeyLeftShift, Edge::Release, 200),
+        ]);
+        assert_eq!(
+            match_tap_alone(Key::KeyShift, max_hold, &history),
+            Some(Capture {
+                class: Key::KeyShift,
+                key: Key::KeyLeftShift
+            })
+        );
+        assert_eq!(match_tap_alone(Key::KeyLeftAlt, max_hold, &history), None);
+    }
+
+    #[test]
+    fn test_match_tap_alone_used_as_modifier() {
+        let history = History::from(vec![
+            key_event(Key::KeyLeftShift, Edge::Press, 0),
+            key_event(Key::KeyA, Edge::Press, 10),
+            key_event(Key::KeyA, Edge::Release, 20),
+            key_event(Key::KeyLeftShift, Edge::Release, 30),
+        ]);
+        assert_eq!(
+            match_tap_alone(Key::KeyShift, Duration::from_millis(300), &history),
+            None
+        );
+    }
+
+    #[test]
+    fn test_match_tap_alone_held_too_long() {
+        let history = History::from(vec![
+            key_event(Key::KeyLeftMeta, Edge::Press, 0),
+            key_event(Key::KeyLeftMeta, Edge::Release, 500),
+        ]);
+        assert_eq!(
+            match_tap_alone(Key::KeyMeta, Duration::from_millis(300), &history),
+            None
+        );
+        assert!(match_tap_alone(Key::KeyMeta, Duration::from_millis(500), &history).is_some());
+    }
+
+    #[test]
+    fn test_match_hold_start() {
+        let mut history = History::from(vec![key_event(Key::KeyF, Edge::Press, 0)]);
+        assert_eq!(
+            match_hold_start(Key::KeyF, &history),
+            Some((Key::KeyF, SystemTime::UNIX_EPOCH))
+        );
+        assert_eq!(match_hold_start(Key::KeyG, &history), None);
+
+        // Auto-repeat does not restart the timer.
+        history.push(key_event(Key::KeyF, Edge::Press, 30));
+        assert_eq!(match_hold_start(Key::KeyF, &history), None);
+    }
+
+    #[test]
+    fn test_still_held() {
+        let since = SystemTime::UNIX_EPOCH + Duration::from_millis(100);
+        let mut history = History::from(vec![
+            key_event(Key::KeyF, Edge::Press, 0),
+            key_event(Key::KeyF, Edge::Release, 50),
+            key_event(Key::KeyF, Edge::Press, 100),
+        ]);
+        assert!(still_held(Key::KeyF, since, &history));
+
+        // Released and pressed again is a different hold.
+        history.push(key_event(Key::KeyF, Edge::Release, 150));
+        history.push(key_event(Key::KeyF, Edge::Press, 200));
+        assert!(!still_held(Key::KeyF, since, &history));
+    }
+
+    #[test]
+    fn test_dual_role_tap() {
+        let dual_role = DualRole::new(Key::KeyCapsLock);
+        let history = History::from(vec![
+            key_event(Key::KeyCapsLock, Edge::Press, 0),
+            key_event(Key::KeyCapsLock, Edge::Release, 100),
+        ]);
+        assert_eq!(
+            resolve_dual_role(&dual_role, &history),
+            Some((
+                Role::Tap,
+                Capture {
+                    class: Key::KeyCapsLock,
+                    key: Key::KeyCapsLock
+                }
+            ))
+        );
+
+        // Released after the tapping term, the timeout already made it a hold.
+        let history = History::from(vec![
+            key_event(Key::KeyCapsLock, Edge::Press, 0),
+            key_event(Key::KeyCapsLock, Edge::Release, 300),
+        ]);
+        assert_eq!(resolve_dual_role(&dual_role, &history), None);
+    }
+
+    #[test]
+    fn test_dual_role_permissive_hold() {
+        let history = History::from(vec![
+            key_event(Key::KeyCapsLock, Edge::Press, 0),
+            key_event(Key::KeyJ, Edge::Press, 50),
+            key_event(Key::KeyJ, Edge::Release, 80),
+        ]);
+        let dual_role = DualRole::new(Key::KeyCapsLock);
+        assert_eq!(resolve_dual_role(&dual_role, &history), None);
+
+        let permissive = DualRole::new(Key::KeyCapsLock).permissive_hold(true);
+        assert_eq!(
+            resolve_dual_role(&permissive, &history).map(|(role, _)| role),
+            Some(Role::Hold)
+        );
+
+        // The release of the dual-role key is no longer a tap.
+        let mut history = history;
+        history.push(key_event(Key::KeyCapsLock, Edge::Release, 120));
+        assert_eq!(resolve_dual_role(&permissive, &history), None);
+        assert_eq!(
+            resolve_dual_role(&dual_role, &history).map(|(role, _)| role),
+            Some(Role::Tap)
+        );
+    }
+
+    #[test]
+    fn test_dual_role_timeout() {
+        let since = SystemTime::UNIX_EPOCH;
+        let mut history = History::from(vec![
+            key_event(Key::KeyCapsLock, Edge::Press, 0),
+            key_event(Key::KeyJ, Edge::Press, 50),
+            key_event(Key::KeyJ, Edge::Release, 80),
+        ]);
+        let dual_role = DualRole::new(Key::KeyCapsLock);
+        let permissive = DualRole::new(Key::KeyCapsLock).permissive_hold(true);
+        assert!(resolve_dual_role_timeout(
+            &dual_role,
+            Key::KeyCapsLock,
+            since,
+            &history
+        ));
+        assert!(!resolve_dual_role_timeout(
+            &permissive,
+            Key::KeyCapsLock,
+            since,
+            &history
+        ));
+
+        history.push(key_event(Key::KeyCapsLock, Edge::Release, 150));
+        assert!(!resolve_dual_role_timeout(
+            &dual_role,
+            Key::KeyCapsLock,
+            since,
+            &history
+        ));
+    }
+
+    #[test]
+    fn test_match_tap_count() {
+        let within = Duration::from_millis(200);
+        let mut history = History::from(vec![
+            key_event(Key::KeyMouseLeft, Edge::Press, 0),
+            key_event(Key::KeyMouseLeft, Edge::Release, 50),
+            key_event(Key::KeyMouseLeft, Edge::Press, 150),
+        ]);
+        assert!(match_tap_count(Key::KeyMouseLeft, 2, within, &history).is_some());
+        assert!(match_tap_count(Key::KeyMouseLeft, 3, within, &history).is_none());
+
+        // The release is not a tap.
+        history.push(key_event(Key::KeyMouseLeft, Edge::Release, 200));
+        assert!(match_tap_count(Key::KeyMouseLeft, 2, within, &history).is_none());
+
+        history.push(key_event(Key::KeyMouseLeft, Edge::Press, 300));
+        assert_eq!(
+            match_tap_count(Key::KeyMouseLeft, 3, within, &history),
+            Some((
+                Key::KeyMouseLeft,
+                SystemTime::UNIX_EPOCH + Duration::from_millis(300)
+            ))
+        );
+        let second = SystemTime::UNIX_EPOCH + Duration::from_millis(150);
+        assert!(tap_run_extended(Key::KeyMouseLeft, second, &history));
+    }
+
+    #[test]
+    fn test_match_tap_count_breaks_run() {
+        let within = Duration::from_millis(200);
+        // Too slow, then interrupted by another key, then auto-repeat.
+        let history = History::from(vec![
+            key_event(Key::KeyA, Edge::Press, 0),
+            key_event(Key::KeyA, Edge::Release, 10),
+            key_event(Key::KeyA, Edge::Press, 500),
+            key_event(Key::KeyA, Edge::Release, 505),
+            key_event(Key::KeyB, Edge::Press, 510),
+            key_event(Key::KeyA, Edge::Press, 520),
+        ]);
+        assert!(match_tap_count(Key::KeyA, 1, within, &history).is_some());
+        assert!(match_tap_count(Key::KeyA, 2, within, &history).is_none());
+
+        let mut history = History::from(vec![
+            key_event(Key::KeyA, Edge::Press, 0),
+            key_event(Key::KeyA, Edge::Press, 30),
+        ]);
+        assert!(match_tap_count(Key::KeyA, 2, within, &history).is_none());
+        history.push(key_event(Key::KeyA, Edge::Release, 60));
+        history.push(key_event(Key::KeyA, Edge::Press, 90));
+        assert!(match_tap_count(Key::KeyA, 2, within, &history).is_some());
+    }
+}